@@ -4,8 +4,9 @@ use std::io::{Cursor, Read, Write};
 use std::path::Path;
 
 const PLANTUML_VERSION: &str = "1.2025.10";
+// The -pdf build of the JAR bundles Apache FOP and Batik, which `-tpdf` needs
 const PLANTUML_JAR_URL: &str =
-    "https://github.com/plantuml/plantuml/releases/download/v1.2025.10/plantuml-1.2025.10.jar";
+    "https://github.com/plantuml/plantuml/releases/download/v1.2025.10/plantuml-pdf-1.2025.10.jar";
 
 // Eclipse Temurin JRE 21 URLs for each platform
 const JRE_URL_WINDOWS_X64: &str = "https://github.com/adoptium/temurin21-binaries/releases/download/jdk-21.0.5%2B11/OpenJDK21U-jre_x64_windows_hotspot_21.0.5_11.zip";
@@ -17,6 +18,12 @@ const JRE_URL_MACOS_AARCH64: &str = "https://github.com/adoptium/temurin21-binar
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
+    // Expose the target triple so runtime errors can say which bundle was built
+    println!(
+        "cargo:rustc-env=PLANTUML_RS_TARGET={}",
+        env::var("TARGET").unwrap()
+    );
+
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
     let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
 
@@ -48,6 +55,7 @@ fn main() {
             "cargo:warning=PlantUML bundle already exists at {:?}",
             bundle_zip
         );
+        emit_bundle_hash(&bundle_zip);
         return;
     }
 
@@ -79,6 +87,25 @@ fn main() {
     }
 
     println!("cargo:warning=Bundle created at {:?}", bundle_zip);
+    emit_bundle_hash(&bundle_zip);
+}
+
+/// Expose a content hash of the bundle so the runtime cache is keyed by what
+/// was bundled rather than by crate version.
+fn emit_bundle_hash(bundle_zip: &Path) {
+    use sha2::{Digest, Sha256};
+
+    println!("cargo:rerun-if-changed={}", bundle_zip.display());
+
+    let mut file = File::open(bundle_zip).expect("Failed to open bundle ZIP");
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).expect("Failed to hash bundle ZIP");
+    let hash: String = hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    println!("cargo:rustc-env=PLANTUML_RS_BUNDLE_HASH={}", hash);
 }
 
 fn download_file(url: &str) -> Vec<u8> {
@@ -0,0 +1,233 @@
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::batch::{render_dir, BatchOptions, BatchReport};
+use crate::error::{PlantUmlError, Result};
+
+/// Distinguishes temp directories created concurrently by the same process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How many names are tried before giving up on creating a temp directory
+const MAX_TEMP_ATTEMPTS: usize = 16;
+
+/// Render every diagram in a ZIP archive to files in `dst`.
+///
+/// The archive is unpacked into a private temporary directory, so
+/// `!include` directives between archive members resolve as they would on
+/// disk. Outputs mirror the archive layout according to `options`, and the
+/// `source` paths in the returned report are the member names inside the
+/// archive. The temporary directory is always removed afterwards.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::path::Path;
+/// use plantuml::BatchOptions;
+///
+/// let archive = File::open("diagrams.zip").unwrap();
+/// let report = plantuml::render_archive(
+///     archive,
+///     Path::new("out"),
+///     &BatchOptions::default(),
+/// ).unwrap();
+/// ```
+pub fn render_archive(
+    archive: impl Read + Seek,
+    dst: &Path,
+    options: &BatchOptions,
+) -> Result<BatchReport> {
+    let temp = TempDir::new()?;
+    unpack(archive, temp.path())?;
+
+    let mut report = render_dir(temp.path(), dst, options)?;
+    for file in &mut report.files {
+        if let Ok(member) = file.source.strip_prefix(temp.path()) {
+            file.source = member.to_path_buf();
+        }
+    }
+
+    Ok(report)
+}
+
+/// Extract all members of a ZIP archive below `dir`.
+fn unpack(archive: impl Read + Seek, dir: &Path) -> Result<()> {
+    let invalid = |e: zip::result::ZipError| PlantUmlError::ArchiveRead(io::Error::other(e));
+    let mut archive = zip::ZipArchive::new(archive).map_err(invalid)?;
+
+    for i in 0..archive.len() {
+        let mut member = archive.by_index(i).map_err(invalid)?;
+
+        // Refuse members that would escape the target directory
+        let Some(name) = member.enclosed_name() else {
+            return Err(PlantUmlError::ArchiveRead(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsafe path in archive: {}", member.name()),
+            )));
+        };
+        let path = dir.join(name);
+
+        if member.is_dir() {
+            fs::create_dir_all(&path).map_err(PlantUmlError::ArchiveRead)?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(PlantUmlError::ArchiveRead)?;
+        }
+        let mut file = fs::File::create(&path).map_err(PlantUmlError::ArchiveRead)?;
+        io::copy(&mut member, &mut file).map_err(PlantUmlError::ArchiveRead)?;
+    }
+
+    Ok(())
+}
+
+/// A uniquely named temporary directory, removed on drop (including during unwinding).
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> Result<Self> {
+        let unique = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let prefix = format!("plantuml-rs-{}-{}", std::process::id(), unique);
+
+        Self::create_in(&std::env::temp_dir(), &prefix)
+    }
+
+    /// Create a fresh private directory named `prefix` in `parent`.
+    ///
+    /// An existing path, which may be a directory or symlink planted by
+    /// another local user, is never reused: the name gets a random suffix
+    /// and creation is retried instead.
+    fn create_in(parent: &Path, prefix: &str) -> Result<Self> {
+        let mut path = parent.join(prefix);
+
+        for _ in 0..MAX_TEMP_ATTEMPTS {
+            match create_private_dir(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    path = parent.join(format!("{}-{:016x}", prefix, random_suffix()));
+                }
+                Err(source) => return Err(PlantUmlError::OutputWrite { path, source }),
+            }
+        }
+
+        Err(PlantUmlError::OutputWrite {
+            path,
+            source: io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "no unused temporary directory name found",
+            ),
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Create `path` as a new directory only the current user can access,
+/// failing if anything already exists there.
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)
+}
+
+/// A random value for temp directory names, from std's randomly keyed hasher.
+fn random_suffix() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn build_zip(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[test]
+    fn test_render_archive() {
+        let archive = build_zip(&[
+            ("common.iuml", "Shared -> Style\n"),
+            (
+                "a.puml",
+                "@startuml\n!include common.iuml\nA -> B\n@enduml\n",
+            ),
+            (
+                "sub/b.puml",
+                "@startuml\n!include ../common.iuml\nB -> C\n@enduml\n",
+            ),
+        ]);
+        let dst = std::env::temp_dir().join(format!("plantuml-rs-archive-{}", std::process::id()));
+
+        let report = render_archive(archive, &dst, &BatchOptions::default()).unwrap();
+        assert_eq!(report.rendered(), 2, "Report: {:?}", report);
+        assert_eq!(report.files[0].source, Path::new("a.puml"));
+        for output in [dst.join("a.svg"), dst.join("sub").join("b.svg")] {
+            let svg = fs::read_to_string(output).unwrap();
+            assert!(svg.contains("Shared"), "Included member should be rendered");
+        }
+
+        let _ = fs::remove_dir_all(&dst);
+    }
+
+    #[test]
+    fn test_temp_dir_removed_on_drop() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().to_path_buf();
+        assert!(path.is_dir());
+
+        drop(temp);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_temp_dir_skips_existing_path() {
+        let parent =
+            std::env::temp_dir().join(format!("plantuml-rs-tmptest-{}", std::process::id()));
+        fs::create_dir_all(&parent).unwrap();
+        let planted = parent.join("taken");
+        fs::create_dir(&planted).unwrap();
+
+        let temp = TempDir::create_in(&parent, "taken").unwrap();
+        assert_ne!(temp.path(), planted, "An existing path must not be reused");
+        assert!(temp.path().is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(temp.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        drop(temp);
+        let _ = fs::remove_dir_all(&parent);
+    }
+}
@@ -0,0 +1,327 @@
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{ChildStdin, Command};
+
+use crate::error::{PlantUmlError, Result};
+use crate::executor::{
+    check_input, command, exit_error, is_graphviz_missing, is_transient, smetana_can_layout,
+    strip_jvm_noise,
+};
+use crate::input::normalize_source;
+use crate::options::RenderOptions;
+use crate::output;
+
+/// Render PlantUML syntax to an SVG string without blocking the runtime.
+///
+/// The async counterpart of [`render`](crate::render). The JVM runs as a
+/// `tokio::process` child and is killed if the future is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn example() {
+/// let svg = plantuml::render_async("@startuml\nAlice -> Bob: Hello\n@enduml")
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub async fn render_async(plantuml: &str) -> Result<String> {
+    let svg = render_with_options_async(plantuml, &RenderOptions::new()).await?;
+
+    String::from_utf8(svg).map_err(PlantUmlError::InvalidUtf8)
+}
+
+/// Render PlantUML syntax with the given options without blocking the runtime.
+///
+/// The async counterpart of [`render_with_options`](crate::render_with_options).
+/// SVG post-processing options ([`RenderOptions::fit`],
+/// [`RenderOptions::provenance`]) are applied as well.
+pub async fn render_with_options_async(plantuml: &str, options: &RenderOptions) -> Result<Vec<u8>> {
+    let source = options.prepare_source(plantuml);
+    let output = execute(source.as_bytes().to_vec(), options).await?;
+
+    crate::postprocess(output, Some(plantuml), options)
+}
+
+/// Render a PlantUML file to an SVG file without blocking the runtime.
+///
+/// The async counterpart of [`render_file`](crate::render_file).
+pub async fn render_file_async(input: &Path, output: &Path) -> Result<()> {
+    let plantuml =
+        tokio::fs::read_to_string(input)
+            .await
+            .map_err(|source| PlantUmlError::InputRead {
+                path: input.to_path_buf(),
+                source,
+            })?;
+
+    let options = RenderOptions::new().source_file(input);
+    let svg = execute(normalize_source(&plantuml).as_bytes().to_vec(), &options).await?;
+
+    write_atomic(output.to_path_buf(), svg).await
+}
+
+/// Render PlantUML syntax to an SVG file without blocking the runtime.
+///
+/// The async counterpart of [`render_to_file`](crate::render_to_file).
+pub async fn render_to_file_async(plantuml: &str, output: &Path) -> Result<()> {
+    let svg = execute(plantuml.as_bytes().to_vec(), &RenderOptions::new()).await?;
+
+    write_atomic(output.to_path_buf(), svg).await
+}
+
+async fn write_atomic(path: PathBuf, bytes: Vec<u8>) -> Result<()> {
+    let target = path.clone();
+    tokio::task::spawn_blocking(move || output::write_atomic(&path, &bytes))
+        .await
+        .map_err(|e| PlantUmlError::OutputWrite {
+            path: target,
+            source: std::io::Error::other(e),
+        })?
+}
+
+/// Async version of [`executor::execute`](crate::executor::execute), with the
+/// same validation, timeout, retry and GraphViz fallback behavior.
+async fn execute(input: Vec<u8>, options: &RenderOptions) -> Result<Vec<u8>> {
+    let result = execute_with_retry(&input, options).await;
+    if options.smetana
+        || options.no_graphviz_fallback
+        || !is_graphviz_missing(result.as_deref())
+        || !smetana_can_layout(&input)
+    {
+        return result;
+    }
+
+    tracing::warn!("graphviz not found, retrying with the smetana layout engine");
+    execute_with_retry(&input, &options.clone().smetana(true)).await
+}
+
+async fn execute_with_retry(input: &[u8], options: &RenderOptions) -> Result<Vec<u8>> {
+    let mut delay = options.retry_backoff;
+    let mut attempt = 0;
+
+    loop {
+        let result = match options.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, execute_once(input, options))
+                .await
+                .unwrap_or(Err(PlantUmlError::Timeout(timeout))),
+            None => execute_once(input, options).await,
+        };
+
+        match result {
+            Err(e) if attempt < options.retries && is_transient(&e) => {
+                attempt += 1;
+                tracing::warn!(error = %e, attempt, "plantuml failed, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn execute_once(input: &[u8], options: &RenderOptions) -> Result<Vec<u8>> {
+    // Checking includes reads files, and resolving the bundle may extract
+    // it on first use; both block
+    let command_options = options.clone();
+    let checked_input = input.to_vec();
+    let command = tokio::task::spawn_blocking(move || {
+        check_input(&checked_input, &command_options)?;
+        command(&command_options)
+    })
+    .await
+    .map_err(|e| PlantUmlError::BinaryExtraction(std::io::Error::other(e)))??;
+
+    tracing::debug!(command = ?command, "spawning plantuml");
+
+    let mut command = Command::from(command);
+    // A dropped future is an abandoned render; timeouts always kill
+    command.kill_on_drop(!options.leave_running || options.timeout.is_some());
+    let mut child = command.spawn().map_err(PlantUmlError::ProcessSpawn)?;
+
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    // Write and drain all pipes concurrently so the JVM never blocks on a full pipe
+    let (written, stdout, stderr) = tokio::join!(
+        async {
+            match stdin {
+                Some(stdin) => write_stdin(stdin, input).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match stdout {
+                Some(stdout) => read_stdout(stdout, options.stdout_limit()).await,
+                None => Ok(Vec::new()),
+            }
+        },
+        async {
+            match stderr {
+                Some(stderr) => read_capped(stderr, options.stderr_limit()).await,
+                None => Ok((Vec::new(), 0)),
+            }
+        },
+    );
+
+    let stdout = match stdout {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            // Stop the process instead of waiting for it to finish writing output we discard
+            let _ = child.kill().await;
+            return Err(e);
+        }
+    };
+
+    let (stderr, dropped) = stderr.unwrap_or_default();
+    let mut stderr = String::from_utf8_lossy(&stderr).into_owned();
+    if dropped > 0 {
+        stderr.push_str(&format!(
+            "\n[plantuml-rs: stderr truncated, {} more bytes]",
+            dropped
+        ));
+    }
+    if options.quiet {
+        stderr = strip_jvm_noise(&stderr);
+    }
+
+    let status = child.wait().await.map_err(PlantUmlError::ProcessSpawn)?;
+
+    tracing::debug!(
+        status = %status,
+        stdout_bytes = stdout.len(),
+        stderr = %stderr,
+        "plantuml exited"
+    );
+
+    if !status.success() {
+        return Err(exit_error(status, stderr));
+    }
+
+    written?;
+
+    Ok(stdout)
+}
+
+/// Write all of `input`, reporting how much was written if PlantUML closes its end early.
+async fn write_stdin(mut stdin: ChildStdin, input: &[u8]) -> Result<()> {
+    let mut written = 0;
+
+    while written < input.len() {
+        match stdin.write(&input[written..]).await {
+            Ok(0) => break,
+            Ok(n) => written += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => break,
+            Err(e) => return Err(PlantUmlError::StdinWrite(e)),
+        }
+    }
+
+    if written < input.len() {
+        return Err(PlantUmlError::StdinClosed {
+            written,
+            total: input.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Read stdout, failing once it exceeds `limit` bytes.
+async fn read_stdout(stdout: impl AsyncRead + Unpin, limit: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    stdout
+        .take(limit as u64 + 1)
+        .read_to_end(&mut buf)
+        .await
+        .map_err(PlantUmlError::ProcessSpawn)?;
+
+    if buf.len() > limit {
+        return Err(PlantUmlError::OutputTooLarge { limit });
+    }
+
+    Ok(buf)
+}
+
+/// Read up to `limit` bytes, then drain the rest, returning the number of bytes dropped.
+async fn read_capped(
+    mut reader: impl AsyncRead + Unpin,
+    limit: usize,
+) -> std::io::Result<(Vec<u8>, u64)> {
+    let mut buf = Vec::new();
+    (&mut reader)
+        .take(limit as u64)
+        .read_to_end(&mut buf)
+        .await?;
+    let dropped = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+
+    Ok((buf, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::OutputFormat;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_render_async() {
+        let result = render_async("@startuml\nAlice -> Bob: Hello\n@enduml").await;
+        assert!(result.is_ok(), "Failed to render: {:?}", result);
+        assert!(result.unwrap().contains("<svg"), "Output should be SVG");
+    }
+
+    #[tokio::test]
+    async fn test_render_async_png_with_timeout() {
+        let options = RenderOptions::new()
+            .format(OutputFormat::Png)
+            .timeout(Duration::from_secs(60));
+        let result = render_with_options_async("@startuml\nA -> B\n@enduml", &options).await;
+        assert!(
+            result.unwrap().starts_with(b"\x89PNG"),
+            "Output should be a PNG"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_file_async_invalid_utf8() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-async-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("latin1.puml");
+        std::fs::write(&input, b"@startuml\nA -> \xe9\n@enduml\n").unwrap();
+
+        let async_error = render_file_async(&input, &dir.join("a.svg"))
+            .await
+            .unwrap_err();
+        let sync_error = crate::render_file(&input, &dir.join("b.svg")).unwrap_err();
+        assert_eq!(async_error.to_string(), sync_error.to_string());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_render_file_async_resolves_relative_includes() {
+        let dir =
+            std::env::temp_dir().join(format!("plantuml-rs-async-inc-{}", std::process::id()));
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("common.iuml"), "Shared -> Style\n").unwrap();
+        let input = docs.join("a.puml");
+        std::fs::write(&input, "@startuml\n!include common.iuml\nA -> B\n@enduml\n").unwrap();
+        let output = dir.join("a.svg");
+
+        render_file_async(&input, &output).await.unwrap();
+        let svg = std::fs::read_to_string(&output).unwrap();
+        assert!(svg.contains("Shared"), "Included file should be rendered");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_render_async_syntax_error() {
+        let result = render_async("not a diagram").await;
+        assert!(matches!(result, Err(PlantUmlError::SyntaxError(_))));
+    }
+}
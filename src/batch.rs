@@ -0,0 +1,721 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::binary::sha256_hex;
+use crate::error::{PlantUmlError, Result};
+use crate::includes::{includes, IncludeOptions};
+use crate::input::read_source;
+use crate::inspect::{blocks, DiagramInfo};
+use crate::options::RenderOptions;
+use crate::{executor, output};
+
+/// File extensions treated as renderable diagram sources.
+pub(crate) const SOURCE_EXTENSIONS: &[&str] = &["puml", "plantuml", "pu", "wsd"];
+
+/// Options for rendering a directory of diagrams with [`render_dir`].
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Descend into subdirectories of the source directory.
+    pub recursive: bool,
+    /// Glob patterns (`*`, `?`, `**`) selecting sources, matched against the path
+    /// relative to the source directory. Empty selects the usual diagram
+    /// extensions (`.puml`, `.plantuml`, `.pu`, `.wsd`).
+    pub include: Vec<String>,
+    /// Glob patterns for sources to leave out, applied after `include`.
+    pub exclude: Vec<String>,
+    /// Mirror the source directory layout below the destination.
+    /// If `false`, all outputs are written directly into the destination.
+    pub mirror: bool,
+    /// Skip sources whose output is newer than the source and all of its includes.
+    pub incremental: bool,
+    /// Maximum number of concurrent renders; `0` uses the available parallelism.
+    pub jobs: usize,
+    /// Template for output file names, without extension, e.g. `"{name}-{page}"`.
+    ///
+    /// When set, every `@startX` block in a source is rendered to its own
+    /// file. Available placeholders: `{name}` (source file stem), `{title}`
+    /// (the diagram's `title`, or the stem if it has none), `{kind}` (e.g.
+    /// `uml`, `mindmap`), `{hash}` (12 hex digits of the block's SHA-256) and
+    /// `{page}` (1-based position of the block in the source). `.svg` is
+    /// appended to the expanded name, and if two blocks expand to the same
+    /// file, [`render_dir`] fails before rendering anything. If `None`, each
+    /// source renders to a single file named after it.
+    pub output_template: Option<String>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            mirror: true,
+            incremental: true,
+            jobs: 0,
+            output_template: None,
+        }
+    }
+}
+
+/// Outcome of rendering a single file in a batch.
+#[derive(Debug)]
+pub enum FileStatus {
+    /// The diagram was rendered.
+    Rendered,
+    /// The output was already up to date.
+    Skipped,
+    /// Rendering failed; other files in the batch are unaffected.
+    Failed(PlantUmlError),
+}
+
+/// Result for one source file of a batch.
+#[derive(Debug)]
+pub struct FileReport {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub status: FileStatus,
+}
+
+/// Per-file results of [`render_dir`], in source path order.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub files: Vec<FileReport>,
+}
+
+impl BatchReport {
+    /// Number of files that were rendered.
+    pub fn rendered(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Rendered))
+    }
+
+    /// Number of files skipped because their output was up to date.
+    pub fn skipped(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Skipped))
+    }
+
+    /// Number of files that failed to render.
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, FileStatus::Failed(_)))
+    }
+
+    /// Whether every file rendered or was skipped.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    fn count(&self, f: impl Fn(&FileStatus) -> bool) -> usize {
+        self.files.iter().filter(|r| f(&r.status)).count()
+    }
+}
+
+/// Render every diagram in `src` to an SVG file in `dst`.
+///
+/// The in-process equivalent of pointing the CLI at a directory. A failing
+/// file does not stop the batch; its error is recorded in the returned
+/// report. An `Err` is returned if the source directory can't be read, or
+/// if two sources would be written to the same output, as can happen
+/// without [`mirror`](BatchOptions::mirror).
+///
+/// Relative `!include` targets resolve against each source's directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use plantuml::BatchOptions;
+///
+/// let report = plantuml::render_dir(
+///     Path::new("docs/diagrams"),
+///     Path::new("target/diagrams"),
+///     &BatchOptions::default(),
+/// ).unwrap();
+/// println!("{} rendered, {} failed", report.rendered(), report.failed());
+/// ```
+pub fn render_dir(src: &Path, dst: &Path, options: &BatchOptions) -> Result<BatchReport> {
+    let sources: Vec<PathBuf> = find_sources(src, options.recursive)?
+        .into_iter()
+        .filter(|path| is_selected(&relative_name(src, path), options))
+        .collect();
+    check_collisions(src, dst, &sources, options)?;
+
+    let jobs = match options.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(sources.len().max(1));
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(sources.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(source) = sources.get(i) else {
+                    break;
+                };
+
+                let output = output_path(src, dst, source, options);
+                let reports = match &options.output_template {
+                    Some(template) => render_templated(source, &output, template, options),
+                    None => {
+                        let status = status(render_one(source, &output, options.incremental));
+                        vec![FileReport {
+                            source: source.clone(),
+                            output,
+                            status,
+                        }]
+                    }
+                };
+
+                results.lock().unwrap().extend(reports);
+            });
+        }
+    });
+
+    let mut files = results.into_inner().unwrap();
+    files.sort_by(|a, b| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.output.cmp(&b.output))
+    });
+
+    Ok(BatchReport { files })
+}
+
+/// Fail if two sources, or two diagram blocks with an output template, map
+/// to the same output, which would race to overwrite it.
+///
+/// With a template, only the expanded names count: the template may well
+/// tell apart sources whose default outputs clash. Sources that can't be
+/// read are left for the render to report.
+fn check_collisions(
+    src: &Path,
+    dst: &Path,
+    sources: &[PathBuf],
+    options: &BatchOptions,
+) -> Result<()> {
+    let mut seen = std::collections::HashMap::new();
+
+    for source in sources {
+        let default_output = output_path(src, dst, source, options);
+        let outputs = match &options.output_template {
+            Some(template) => match read_source(source) {
+                Ok(plantuml) => block_outputs(source, &default_output, template, &plantuml)
+                    .into_iter()
+                    .map(|(output, _)| output)
+                    .collect(),
+                Err(_) => continue,
+            },
+            None => vec![default_output],
+        };
+
+        for output in outputs {
+            if let Some(first) = seen.insert(output.clone(), source) {
+                return Err(PlantUmlError::OutputCollision {
+                    output,
+                    first: first.clone(),
+                    second: source.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn status(result: Result<bool>) -> FileStatus {
+    match result {
+        Ok(true) => FileStatus::Rendered,
+        Ok(false) => FileStatus::Skipped,
+        Err(e) => FileStatus::Failed(e),
+    }
+}
+
+/// Render a single source, returning whether it was rendered or skipped.
+fn render_one(source: &Path, output: &Path, incremental: bool) -> Result<bool> {
+    let plantuml = read_source(source)?;
+
+    if incremental && is_current(source, &plantuml, output)? {
+        return Ok(false);
+    }
+
+    write_output(source, &plantuml, output)?;
+
+    Ok(true)
+}
+
+/// Render each diagram block of `source` to a file named by `template`.
+///
+/// `default_output` is the output [`render_dir`] would use without a
+/// template; its directory receives the files, and it is reported if the
+/// source can't be read.
+fn render_templated(
+    source: &Path,
+    default_output: &Path,
+    template: &str,
+    options: &BatchOptions,
+) -> Vec<FileReport> {
+    let failed = |e| {
+        vec![FileReport {
+            source: source.to_path_buf(),
+            output: default_output.to_path_buf(),
+            status: FileStatus::Failed(e),
+        }]
+    };
+    let plantuml = match read_source(source) {
+        Ok(plantuml) => plantuml,
+        Err(e) => return failed(e),
+    };
+    // Includes are shared by all blocks, so trace them once
+    let deps = if options.incremental {
+        match source_dependencies(source, &plantuml) {
+            Ok(deps) => deps,
+            Err(e) => return failed(e),
+        }
+    } else {
+        Vec::new()
+    };
+
+    block_outputs(source, default_output, template, &plantuml)
+        .into_iter()
+        .map(|(output, text)| {
+            let result = if options.incremental && is_fresh(&output, &deps) {
+                Ok(false)
+            } else {
+                write_output(source, &text, &output).map(|()| true)
+            };
+
+            FileReport {
+                source: source.to_path_buf(),
+                output,
+                status: status(result),
+            }
+        })
+        .collect()
+}
+
+/// The output path and text of each diagram block in `plantuml`, named by `template`.
+fn block_outputs(
+    source: &Path,
+    default_output: &Path,
+    template: &str,
+    plantuml: &str,
+) -> Vec<(PathBuf, String)> {
+    let dir = default_output.parent().unwrap_or(Path::new(""));
+    let name = source.file_stem().unwrap_or_default().to_string_lossy();
+
+    blocks(plantuml)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (info, text))| {
+            // Appended rather than set, as titles may contain dots
+            let file_name = expand_template(template, &name, &info, &text, i + 1);
+            (dir.join(format!("{}.svg", file_name)), text)
+        })
+        .collect()
+}
+
+/// Fill in the placeholders of an output name template for one diagram block.
+fn expand_template(
+    template: &str,
+    name: &str,
+    info: &DiagramInfo,
+    text: &str,
+    page: usize,
+) -> String {
+    let hash = sha256_hex(&mut text.as_bytes()).unwrap_or_default();
+    let title = info
+        .title
+        .as_deref()
+        .map_or_else(|| name.to_string(), sanitize);
+
+    template
+        .replace("{name}", name)
+        .replace("{title}", &title)
+        .replace("{kind}", info.kind.tag())
+        .replace("{hash}", &hash[..hash.len().min(12)])
+        .replace("{page}", &page.to_string())
+}
+
+/// Make a diagram title usable as a file name.
+fn sanitize(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_matches(['_', '.'])
+        .to_string()
+}
+
+/// The source file and every file it includes, for freshness checks.
+pub(crate) fn source_dependencies(source: &Path, plantuml: &str) -> Result<Vec<PathBuf>> {
+    let opts = IncludeOptions {
+        base_dir: source.parent().map(Path::to_path_buf),
+        ..Default::default()
+    };
+    let mut deps = includes(plantuml, &opts)?;
+    deps.insert(0, source.to_path_buf());
+
+    Ok(deps)
+}
+
+/// Whether `output` is newer than `source` and everything it includes.
+fn is_current(source: &Path, plantuml: &str, output: &Path) -> Result<bool> {
+    Ok(is_fresh(output, &source_dependencies(source, plantuml)?))
+}
+
+fn write_output(source: &Path, plantuml: &str, output: &Path) -> Result<()> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| PlantUmlError::OutputWrite {
+            path: parent.to_path_buf(),
+            source: e,
+        })?;
+    }
+
+    let options = RenderOptions::new().source_file(source);
+    let svg = executor::execute(plantuml.as_bytes(), &options)?;
+
+    output::write_atomic(output, &svg)
+}
+
+fn output_path(src: &Path, dst: &Path, source: &Path, options: &BatchOptions) -> PathBuf {
+    let relative = if options.mirror {
+        source.strip_prefix(src).unwrap_or(source)
+    } else {
+        Path::new(source.file_name().unwrap_or_default())
+    };
+
+    dst.join(relative).with_extension("svg")
+}
+
+/// Path of `path` relative to `base`, with `/` separators for glob matching.
+fn relative_name(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_selected(name: &str, options: &BatchOptions) -> bool {
+    let included = if options.include.is_empty() {
+        is_source(Path::new(name))
+    } else {
+        options.include.iter().any(|p| glob_match(p, name))
+    };
+
+    included && !options.exclude.iter().any(|p| glob_match(p, name))
+}
+
+/// Match `name` against a glob supporting `*`, `?` and `**` (any number of directories).
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn segments(s: &str) -> Vec<&str> {
+        s.split('/').filter(|s| !s.is_empty()).collect()
+    }
+
+    fn match_segments(pattern: &[&str], name: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some((&"**", rest)) => (0..=name.len()).any(|i| match_segments(rest, &name[i..])),
+            Some((p, rest)) => name.split_first().is_some_and(|(n, name)| {
+                match_segment(p.as_bytes(), n.as_bytes()) && match_segments(rest, name)
+            }),
+        }
+    }
+
+    fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some((b'*', rest)) => (0..=name.len()).any(|i| match_segment(rest, &name[i..])),
+            Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+        }
+    }
+
+    match_segments(&segments(pattern), &segments(name))
+}
+
+/// Collect diagram sources under `dir`, in a stable order.
+///
+/// Subdirectories are only visited if `recursive` is set, and symlinked
+/// directories never are. When no include patterns are given, callers
+/// filter the result with [`is_source`].
+pub(crate) fn find_sources(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|source| PlantUmlError::InputRead {
+            path: dir.clone(),
+            source,
+        })?;
+
+        for entry in entries {
+            let entry = entry.map_err(|source| PlantUmlError::InputRead {
+                path: dir.clone(),
+                source,
+            })?;
+            let path = entry.path();
+            // Not followed through symlinks, so a link cycle can't recurse forever
+            let file_type = entry
+                .file_type()
+                .map_err(|source| PlantUmlError::InputRead {
+                    path: path.clone(),
+                    source,
+                })?;
+
+            if file_type.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if !(file_type.is_symlink() && path.is_dir()) {
+                sources.push(path);
+            }
+        }
+    }
+
+    sources.sort();
+    Ok(sources)
+}
+
+pub(crate) fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext))
+}
+
+/// Check whether `output` exists and is newer than every input.
+pub(crate) fn is_fresh<'a>(output: &Path, inputs: impl IntoIterator<Item = &'a PathBuf>) -> bool {
+    let Some(output_time) = modified(output) else {
+        return false;
+    };
+
+    inputs
+        .into_iter()
+        .all(|input| modified(input).is_some_and(|t| t <= output_time))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.puml", "a.puml"));
+        assert!(!glob_match("*.puml", "sub/a.puml"));
+        assert!(glob_match("**/*.puml", "a.puml"));
+        assert!(glob_match("**/*.puml", "x/y/a.puml"));
+        assert!(glob_match("docs/**", "docs/x/a.puml"));
+        assert!(glob_match("seq_??.pu", "seq_01.pu"));
+        assert!(!glob_match("seq_??.pu", "seq_1.pu"));
+    }
+
+    #[test]
+    fn test_render_dir_output_template() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-template-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("flows.puml"),
+            "@startuml\ntitle Login flow\nA -> B\n@enduml\n@startmindmap\n* root\n@endmindmap\n",
+        )
+        .unwrap();
+
+        let options = BatchOptions {
+            output_template: Some("{name}-{page}-{kind}-{title}".to_string()),
+            ..Default::default()
+        };
+        let report = render_dir(&src, &dst, &options).unwrap();
+        assert_eq!(report.rendered(), 2, "Report: {:?}", report);
+        assert!(dst.join("flows-1-uml-Login_flow.svg").exists());
+        assert!(dst.join("flows-2-mindmap-flows.svg").exists());
+
+        let report = render_dir(&src, &dst, &options).unwrap();
+        assert_eq!(report.skipped(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir_output_template_keeps_dots() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-tpl-dots-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("flows.puml"),
+            "@startuml\ntitle Release 1.2 flow\nA -> B\n@enduml\n",
+        )
+        .unwrap();
+
+        let options = BatchOptions {
+            output_template: Some("{title}".to_string()),
+            ..Default::default()
+        };
+        let report = render_dir(&src, &dst, &options).unwrap();
+        assert_eq!(report.rendered(), 1, "Report: {:?}", report);
+        assert!(dst.join("Release_1.2_flow.svg").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir_output_template_collision() {
+        let dir =
+            std::env::temp_dir().join(format!("plantuml-rs-tpl-clash-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("flows.puml"),
+            "@startuml\nA -> B\n@enduml\n@startuml\nB -> C\n@enduml\n",
+        )
+        .unwrap();
+
+        let options = BatchOptions {
+            output_template: Some("{name}".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            render_dir(&src, &dst, &options),
+            Err(PlantUmlError::OutputCollision { .. })
+        ));
+        assert!(!dst.exists(), "Nothing should be rendered on a collision");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir_template_disambiguates_flat_outputs() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-tpl-flat-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(src.join("x")).unwrap();
+        fs::create_dir_all(src.join("y")).unwrap();
+        fs::write(src.join("x").join("a.puml"), "@startuml\nA -> B\n@enduml\n").unwrap();
+        fs::write(src.join("y").join("a.puml"), "@startuml\nB -> C\n@enduml\n").unwrap();
+
+        let options = BatchOptions {
+            mirror: false,
+            output_template: Some("{hash}".to_string()),
+            ..Default::default()
+        };
+        let report = render_dir(&src, &dst, &options).unwrap();
+        assert_eq!(report.rendered(), 2, "Report: {:?}", report);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-batch-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.puml"), "@startuml\nA -> B\n@enduml\n").unwrap();
+        fs::write(src.join("broken.puml"), "not a diagram\n").unwrap();
+        fs::write(src.join("notes.txt"), "ignored\n").unwrap();
+        fs::write(
+            src.join("nested").join("b.puml"),
+            "@startuml\nB -> C\n@enduml\n",
+        )
+        .unwrap();
+
+        let options = BatchOptions {
+            exclude: vec!["**/broken.*".to_string()],
+            ..Default::default()
+        };
+        let report = render_dir(&src, &dst, &options).unwrap();
+        assert_eq!(report.rendered(), 2);
+        assert!(dst.join("nested").join("b.svg").exists());
+
+        let report = render_dir(&src, &dst, &BatchOptions::default()).unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.skipped(), 2, "Unchanged files should be skipped");
+        assert_eq!(
+            report.failed(),
+            1,
+            "Broken file should fail without stopping the batch"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir_resolves_relative_includes() {
+        let dir =
+            std::env::temp_dir().join(format!("plantuml-rs-batch-inc-{}", std::process::id()));
+        let src = dir.join("src");
+        let dst = dir.join("dst");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("common.iuml"), "Shared -> Style\n").unwrap();
+        fs::write(
+            src.join("nested").join("b.puml"),
+            "@startuml\n!include ../common.iuml\nB -> C\n@enduml\n",
+        )
+        .unwrap();
+
+        let report = render_dir(&src, &dst, &BatchOptions::default()).unwrap();
+        assert!(report.is_success(), "Report: {:?}", report);
+        let svg = fs::read_to_string(dst.join("nested").join("b.svg")).unwrap();
+        assert!(
+            svg.contains("Shared"),
+            "Included content should be rendered"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_dir_flat_output_collision() {
+        let dir =
+            std::env::temp_dir().join(format!("plantuml-rs-batch-flat-{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("x")).unwrap();
+        fs::create_dir_all(src.join("y")).unwrap();
+        fs::write(src.join("x").join("a.puml"), "@startuml\nA -> B\n@enduml\n").unwrap();
+        fs::write(src.join("y").join("a.puml"), "@startuml\nB -> C\n@enduml\n").unwrap();
+
+        let options = BatchOptions {
+            mirror: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            render_dir(&src, &dir.join("dst"), &options),
+            Err(PlantUmlError::OutputCollision { .. })
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_sources_skips_symlinked_dirs() {
+        let dir =
+            std::env::temp_dir().join(format!("plantuml-rs-batch-link-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a.puml"), "").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub").join("loop")).unwrap();
+
+        let sources = find_sources(&dir, true).unwrap();
+        assert_eq!(sources, vec![dir.join("sub").join("a.puml")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,10 +2,44 @@
 //!
 //! This binary passes all arguments directly to the bundled PlantUML JAR.
 //! Run `plantuml-rs --help` to see PlantUML's help.
+//!
+//! The only exception is `plantuml-rs extract-bundle <dir>`, which extracts
+//! the bundled JRE and JAR into `<dir>` (e.g. while building a container
+//! image) for use via `PLANTUML_RS_BUNDLE_DIR`, and
+//! `plantuml-rs stress [renders] [workers]`, which runs
+//! [`plantuml::load_test`] against a renderer pool and fails if it leaks.
+//!
+//! On Unix, SIGINT and SIGTERM are forwarded to PlantUML, which is killed
+//! if it doesn't exit promptly, and the CLI exits with `128 + signal`.
 
-use std::process::{Command, ExitCode};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, ExitCode, ExitStatus};
 
 fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("extract-bundle") {
+        return match args.nth(1) {
+            Some(dir) => extract_bundle(Path::new(&dir)),
+            None => {
+                eprintln!("Usage: plantuml-rs extract-bundle <dir>");
+                ExitCode::from(2)
+            }
+        };
+    }
+    if args.peek().map(String::as_str) == Some("stress") {
+        let counts: Result<Vec<usize>, _> = args.skip(1).map(|arg| arg.parse()).collect();
+        return match counts.as_deref() {
+            Ok([]) => stress(1000, 4),
+            Ok([renders]) => stress(*renders, 4),
+            Ok([renders, workers]) if *workers > 0 => stress(*renders, *workers),
+            _ => {
+                eprintln!("Usage: plantuml-rs stress [renders] [workers]");
+                ExitCode::from(2)
+            }
+        };
+    }
+
     // Get bundle paths (extracts on first run)
     let paths = match plantuml::get_bundle_paths() {
         Ok(p) => p,
@@ -18,24 +52,197 @@ fn main() -> ExitCode {
     // Collect all arguments (skip the program name)
     let args: Vec<String> = std::env::args().skip(1).collect();
 
+    // Catch Ctrl-C and termination requests so they reach PlantUML too
+    #[cfg(unix)]
+    signals::install();
+
     // Run: java -jar plantuml.jar <args...>
-    let status = Command::new(&paths.java_exe)
+    let child = Command::new(&paths.java_exe)
         .arg("-jar")
         .arg(&paths.plantuml_jar)
         .args(&args)
-        .status();
-
-    match status {
-        Ok(s) => {
-            if let Some(code) = s.code() {
-                ExitCode::from(code as u8)
-            } else {
-                ExitCode::from(1)
-            }
-        }
+        .spawn();
+
+    match child.and_then(|mut child| wait(&mut child)) {
+        Ok(code) => code,
         Err(e) => {
             eprintln!("Error running PlantUML: {}", e);
             ExitCode::from(1)
         }
     }
 }
+
+/// Wait for PlantUML to finish and map its status to our exit code.
+///
+/// If the CLI receives SIGINT or SIGTERM, the signal is forwarded to
+/// PlantUML, which is killed if it doesn't exit within
+/// [`signals::GRACE_PERIOD`], and the CLI exits with `128 + signal`.
+#[cfg(unix)]
+fn wait(child: &mut Child) -> std::io::Result<ExitCode> {
+    loop {
+        if let Some(signal) = signals::received() {
+            signals::terminate(child, signal)?;
+            let _ = std::io::stdout().flush();
+            return Ok(ExitCode::from((128 + signal) as u8));
+        }
+
+        if let Some(status) = child.try_wait()? {
+            return Ok(exit_code(&status));
+        }
+        std::thread::sleep(signals::POLL_INTERVAL);
+    }
+}
+
+#[cfg(not(unix))]
+fn wait(child: &mut Child) -> std::io::Result<ExitCode> {
+    Ok(exit_code(&child.wait()?))
+}
+
+fn exit_code(status: &ExitStatus) -> ExitCode {
+    match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => signal_exit_code(status),
+    }
+}
+
+/// Extract the bundle into `dir` and print the environment to use it.
+fn extract_bundle(dir: &Path) -> ExitCode {
+    match plantuml::extract_bundle_to(dir) {
+        Ok(paths) => {
+            println!("Extracted PlantUML bundle to {}", dir.display());
+            println!("  java: {}", paths.java_exe.display());
+            println!("  jar:  {}", paths.plantuml_jar.display());
+            println!("Use it with {}={}", plantuml::BUNDLE_DIR_ENV, dir.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error extracting PlantUML bundle: {}", e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Load test a pool of `workers` renderers and report whether it leaked.
+///
+/// A warm-up run starts the workers first, so that the measured run only
+/// grows the file descriptor count if something leaks.
+fn stress(renders: usize, workers: usize) -> ExitCode {
+    let sources = [
+        "@startuml\nAlice -> Bob: Hello\n@enduml",
+        "@startuml\nclass Foo\nclass Bar\nFoo --> Bar\n@enduml",
+        "@startuml\nstart\n:Step;\nstop\n@enduml",
+    ];
+    let pool = plantuml::RendererPool::new(workers);
+    let concurrency = workers * 2;
+
+    plantuml::load_test(&pool, &sources, workers * 4, concurrency);
+    let report = plantuml::load_test(&pool, &sources, renders, concurrency);
+
+    println!(
+        "{} renders on {} workers in {:.2?}",
+        report.renders, workers, report.elapsed
+    );
+    println!("  failures:          {}", report.failures);
+    println!("  processes started: {}", report.processes_started);
+    println!("  processes leaked:  {}", report.processes_leaked);
+    if let Some((before, after)) = report.open_fds {
+        println!("  open fds:          {} -> {}", before, after);
+    }
+    if let Some((before, after)) = report.resident_bytes {
+        println!(
+            "  resident memory:   {} KiB -> {} KiB",
+            before / 1024,
+            after / 1024
+        );
+    }
+    if let Some(e) = &report.first_error {
+        eprintln!("First error: {}", e);
+    }
+
+    if report.is_clean() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+/// Exit code for a PlantUML process that was terminated by a signal.
+///
+/// Follows the shell convention of `128 + signal` so that an interrupted
+/// run (e.g. Ctrl-C, exit code 130) is distinguishable from a PlantUML error.
+#[cfg(unix)]
+fn signal_exit_code(status: &ExitStatus) -> ExitCode {
+    use std::os::unix::process::ExitStatusExt;
+
+    match status.signal() {
+        Some(signal) => ExitCode::from((128 + signal) as u8),
+        None => ExitCode::from(1),
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_exit_code(_status: &ExitStatus) -> ExitCode {
+    ExitCode::from(1)
+}
+
+/// SIGINT/SIGTERM handling for the passthrough.
+#[cfg(unix)]
+mod signals {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How often the child and pending signals are checked
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// How long PlantUML gets to exit after a forwarded signal before it is killed
+    pub const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+    /// The last signal received, or 0
+    static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn record(signal: libc::c_int) {
+        RECEIVED.store(signal, Ordering::SeqCst);
+    }
+
+    /// Record SIGINT and SIGTERM instead of dying on them.
+    ///
+    /// Caught signals are reset to their default action in the child on exec.
+    pub fn install() {
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+            unsafe {
+                libc::signal(
+                    signal,
+                    record as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                );
+            }
+        }
+    }
+
+    pub fn received() -> Option<i32> {
+        match RECEIVED.load(Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// Forward `signal` to the child, killing it after [`GRACE_PERIOD`], and reap it.
+    pub fn terminate(child: &mut Child, signal: i32) -> std::io::Result<()> {
+        // SAFETY: kill has no memory-safety preconditions; the child is not reaped yet
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, signal);
+        }
+
+        let deadline = Instant::now() + GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        child.kill()?;
+        child.wait().map(drop)
+    }
+}
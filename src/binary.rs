@@ -1,72 +1,313 @@
 use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::io::{Cursor, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::error::{PlantUmlError, Result};
 
 /// Embedded PlantUML bundle (JRE + JAR)
 const PLANTUML_BUNDLE: &[u8] = include_bytes!("../binaries/plantuml-bundle.zip");
 
-/// Version string for cache directory
+/// Crate version, reported in bundle errors
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Short content hash of the embedded bundle, computed at build time
+const BUNDLE_HASH: &str = env!("PLANTUML_RS_BUNDLE_HASH");
+
+/// Target triple the bundle was built for
+const TARGET: &str = env!("PLANTUML_RS_TARGET");
+
 /// Cached path to the extracted bundle directory
 static EXTRACTED_DIR: OnceCell<PathBuf> = OnceCell::new();
 
+/// Distinguishes staging directories of concurrent extractions in one process
+static STAGING_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Environment variable pointing at a pre-extracted bundle directory
+pub const BUNDLE_DIR_ENV: &str = "PLANTUML_RS_BUNDLE_DIR";
+
+/// SHA-256 of the embedded PlantUML JAR, computed on first use
+static JAR_SHA256: OnceCell<String> = OnceCell::new();
+
+/// Bundle configuration set via [`configure_bundle`]
+static CONFIG: OnceCell<BundleConfig> = OnceCell::new();
+
 /// Paths to the Java executable and PlantUML JAR
 pub struct BundlePaths {
     pub java_exe: PathBuf,
     pub plantuml_jar: PathBuf,
 }
 
+/// Controls where the bundle is extracted to, or where an extracted copy is found.
+#[derive(Debug, Clone)]
+pub struct BundleConfig {
+    /// Extract into this directory instead of the user's cache directory.
+    pub cache_dir: Option<PathBuf>,
+    /// Use a bundle that was already extracted to this directory; nothing is extracted.
+    pub pre_extracted: Option<PathBuf>,
+    /// If the cache directory is not writable, extract into the system temp directory instead.
+    pub fallback_to_temp: bool,
+    /// Verify the extracted JAR against [`bundled_jar_sha256`] before every render,
+    /// so a tampered cache directory can't swap in a different PlantUML.
+    pub verify_jar_checksum: bool,
+}
+
+impl Default for BundleConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            pre_extracted: None,
+            fallback_to_temp: true,
+            verify_jar_checksum: false,
+        }
+    }
+}
+
+impl BundleConfig {
+    /// Default configuration, using a pre-extracted bundle if [`BUNDLE_DIR_ENV`] is set.
+    pub fn from_env() -> Self {
+        Self {
+            pre_extracted: std::env::var_os(BUNDLE_DIR_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            ..Self::default()
+        }
+    }
+}
+
+/// Configure bundle extraction.
+///
+/// Without an explicit configuration, [`BundleConfig::from_env`] is used.
+/// Must be called before the first render; fails with
+/// [`PlantUmlError::BundleAlreadyInitialized`] otherwise.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::BundleConfig;
+/// plantuml::configure_bundle(BundleConfig {
+///     pre_extracted: Some("/opt/plantuml-bundle".into()),
+///     ..Default::default()
+/// }).unwrap();
+/// ```
+pub fn configure_bundle(config: BundleConfig) -> Result<()> {
+    if EXTRACTED_DIR.get().is_some() {
+        return Err(PlantUmlError::BundleAlreadyInitialized);
+    }
+
+    CONFIG
+        .set(config)
+        .map_err(|_| PlantUmlError::BundleAlreadyInitialized)
+}
+
 /// Get the paths to the Java executable and PlantUML JAR.
 ///
 /// The bundle is extracted to the user's cache directory on first call.
 /// Subsequent calls return the cached paths.
 pub fn get_bundle_paths() -> Result<BundlePaths> {
-    let dir = EXTRACTED_DIR.get_or_try_init(extract_bundle)?;
+    let config = CONFIG.get_or_init(BundleConfig::from_env);
+    let dir = EXTRACTED_DIR.get_or_try_init(|| locate_bundle(config))?;
+    let plantuml_jar = dir.join("plantuml.jar");
 
-    #[cfg(windows)]
-    let java_exe = dir.join("jre").join("bin").join("java.exe");
-    #[cfg(not(windows))]
-    let java_exe = dir.join("jre").join("bin").join("java");
+    if config.verify_jar_checksum {
+        verify_jar(&plantuml_jar)?;
+    }
 
     Ok(BundlePaths {
-        java_exe,
+        java_exe: java_exe_in(dir),
+        plantuml_jar,
+    })
+}
+
+/// SHA-256 of the PlantUML JAR embedded in this build, as lowercase hex.
+pub fn bundled_jar_sha256() -> Result<&'static str> {
+    JAR_SHA256
+        .get_or_try_init(|| {
+            let mut archive = open_bundle()?;
+            let mut jar = archive.by_name("plantuml.jar").map_err(bundle_corrupted)?;
+            sha256_hex(&mut jar).map_err(PlantUmlError::BinaryExtraction)
+        })
+        .map(String::as_str)
+}
+
+/// Check the JAR at `path` against the embedded JAR's checksum.
+fn verify_jar(path: &Path) -> Result<()> {
+    let expected = bundled_jar_sha256()?;
+    let mut file = fs::File::open(path).map_err(PlantUmlError::BinaryExtraction)?;
+    let actual = sha256_hex(&mut file).map_err(PlantUmlError::BinaryExtraction)?;
+
+    if actual != expected {
+        return Err(PlantUmlError::JarChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn sha256_hex(reader: &mut impl Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(reader, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Extract the embedded bundle into `dir` and return the resulting paths.
+///
+/// Used to bake the bundle into container images ahead of time; point
+/// [`BUNDLE_DIR_ENV`] at `dir` at runtime to skip first-run extraction.
+pub fn extract_bundle_to(dir: &Path) -> Result<BundlePaths> {
+    let dir = extract_bundle(dir)?;
+
+    Ok(BundlePaths {
+        java_exe: java_exe_in(&dir),
         plantuml_jar: dir.join("plantuml.jar"),
     })
 }
 
-/// Extract the embedded bundle to the cache directory.
-fn extract_bundle() -> Result<PathBuf> {
-    let cache_dir = get_cache_dir()?;
+/// Resolve the bundle directory for `config`, extracting if needed.
+fn locate_bundle(config: &BundleConfig) -> Result<PathBuf> {
+    if let Some(dir) = &config.pre_extracted {
+        if !is_extracted(dir) {
+            return Err(PlantUmlError::BinaryExtraction(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("no extracted plantuml bundle found in '{}'", dir.display()),
+            )));
+        }
+        return Ok(dir.clone());
+    }
+
+    let cache_dir = match &config.cache_dir {
+        Some(dir) => dir.clone(),
+        None => get_cache_dir()?,
+    };
 
+    match extract_bundle(&cache_dir) {
+        Err(PlantUmlError::BinaryExtraction(e)) if config.fallback_to_temp && is_read_only(&e) => {
+            let temp_dir = temp_cache_dir()?;
+            tracing::debug!(
+                cache_dir = %cache_dir.display(),
+                temp_dir = %temp_dir.display(),
+                "cache directory not writable, extracting to temp directory"
+            );
+            extract_bundle(&temp_dir)
+        }
+        result => result,
+    }
+}
+
+fn is_read_only(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+fn java_exe_in(dir: &Path) -> PathBuf {
     #[cfg(windows)]
-    let java_exe = cache_dir.join("jre").join("bin").join("java.exe");
+    let java_exe = dir.join("jre").join("bin").join("java.exe");
     #[cfg(not(windows))]
-    let java_exe = cache_dir.join("jre").join("bin").join("java");
+    let java_exe = dir.join("jre").join("bin").join("java");
+
+    java_exe
+}
+
+/// Check whether `dir` holds a complete extraction.
+///
+/// The JAR is moved into place last, so its presence marks a finished extraction.
+fn is_extracted(dir: &Path) -> bool {
+    java_exe_in(dir).exists() && dir.join("plantuml.jar").exists()
+}
 
-    let jar_path = cache_dir.join("plantuml.jar");
+/// Extract the embedded bundle to `cache_dir`.
+///
+/// The bundle is unpacked into a private staging directory inside
+/// `cache_dir` and then moved into place, so a failed extraction only ever
+/// removes what it wrote itself, and concurrent processes never see a
+/// half-written JRE or JAR.
+fn extract_bundle(cache_dir: &Path) -> Result<PathBuf> {
+    // Validate the central directory up front so a bad build is reported as such,
+    // even when a previous extraction is reused
+    let mut archive = open_bundle()?;
 
-    // If both main files exist, assume extraction is complete
-    if java_exe.exists() && jar_path.exists() {
-        return Ok(cache_dir);
+    if is_extracted(cache_dir) {
+        return Ok(cache_dir.to_path_buf());
     }
 
+    extract_from(&mut archive, cache_dir)?;
+
+    Ok(cache_dir.to_path_buf())
+}
+
+/// Unpack `archive` into `cache_dir` through a staging directory.
+fn extract_from<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, cache_dir: &Path) -> Result<()> {
     // Create cache directory if needed
-    fs::create_dir_all(&cache_dir).map_err(PlantUmlError::BinaryExtraction)?;
+    fs::create_dir_all(cache_dir).map_err(PlantUmlError::BinaryExtraction)?;
+
+    let staging = cache_dir.join(format!(
+        ".staging-{}-{}",
+        std::process::id(),
+        STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir(&staging).map_err(PlantUmlError::BinaryExtraction)?;
+
+    let result = extract_archive(archive, &staging).and_then(|()| install(&staging, cache_dir));
+    let _ = fs::remove_dir_all(&staging);
 
-    // Extract the bundle ZIP
-    let cursor = Cursor::new(PLANTUML_BUNDLE);
-    let mut archive = zip::ZipArchive::new(cursor).map_err(|e| {
-        PlantUmlError::BinaryExtraction(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
-    })?;
+    if let Err(e) = result {
+        return Err(match e {
+            PlantUmlError::BinaryExtraction(io) if io.kind() == ErrorKind::StorageFull => {
+                PlantUmlError::CacheDiskFull {
+                    required: unpacked_size(archive),
+                    available: available_space(cache_dir),
+                    path: cache_dir.to_path_buf(),
+                }
+            }
+            e => e,
+        });
+    }
 
+    Ok(())
+}
+
+/// Move a finished extraction from `staging` into `cache_dir`, JAR last.
+///
+/// Entries another process already moved into place are kept.
+fn install(staging: &Path, cache_dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(staging).map_err(PlantUmlError::BinaryExtraction)?;
+    let mut names = entries
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(PlantUmlError::BinaryExtraction)?;
+    names.sort_by_key(|name| name == "plantuml.jar");
+
+    for name in names {
+        let target = cache_dir.join(&name);
+        if let Err(e) = fs::rename(staging.join(&name), &target) {
+            if !target.exists() {
+                return Err(PlantUmlError::BinaryExtraction(e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write every entry of the bundle archive below `cache_dir`.
+fn extract_archive<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    cache_dir: &Path,
+) -> Result<()> {
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| {
-            PlantUmlError::BinaryExtraction(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
-        })?;
+        let mut file = archive.by_index(i).map_err(bundle_corrupted)?;
 
         let name = file.name().to_string();
 
@@ -105,16 +346,123 @@ fn extract_bundle() -> Result<PathBuf> {
         }
     }
 
-    Ok(cache_dir)
+    Ok(())
+}
+
+/// Total uncompressed size of the bundle contents in bytes.
+fn unpacked_size<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> u64 {
+    (0..archive.len())
+        .filter_map(|i| archive.by_index_raw(i).ok().map(|f| f.size()))
+        .sum()
+}
+
+/// Free space available to unprivileged users on the filesystem holding `path`.
+#[cfg(unix)]
+fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // The target directory may have been removed; measure its nearest existing ancestor
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` is only read on success
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Open the embedded bundle, verifying its central directory and required entries.
+fn open_bundle() -> Result<zip::ZipArchive<Cursor<&'static [u8]>>> {
+    let archive = zip::ZipArchive::new(Cursor::new(PLANTUML_BUNDLE)).map_err(bundle_corrupted)?;
+
+    if archive.index_for_name("plantuml.jar").is_none() {
+        return Err(bundle_corrupted("missing plantuml.jar"));
+    }
+
+    Ok(archive)
+}
+
+fn bundle_corrupted(reason: impl ToString) -> PlantUmlError {
+    PlantUmlError::BundleCorrupted {
+        version: VERSION,
+        target: TARGET,
+        reason: reason.to_string(),
+    }
 }
 
-/// Get the cache directory for this version of the library.
+/// Get the cache directory for the embedded bundle.
 fn get_cache_dir() -> Result<PathBuf> {
-    let base = dirs::cache_dir()
-        .or_else(dirs::data_local_dir)
-        .unwrap_or_else(std::env::temp_dir);
+    match dirs::cache_dir().or_else(dirs::data_local_dir) {
+        Some(base) => Ok(base.join("plantuml-rs").join(cache_key())),
+        None => temp_cache_dir(),
+    }
+}
+
+/// Cache directory for the embedded bundle below the shared temp directory.
+///
+/// On Unix this is a per-user directory with mode 0700, so other users
+/// can't plant a `java` binary there ahead of us.
+fn temp_cache_dir() -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid has no preconditions and cannot fail
+        let uid = unsafe { libc::getuid() };
+        let base = std::env::temp_dir().join(format!("plantuml-rs-{}", uid));
+        private_dir(&base, uid)?;
+
+        Ok(base.join(cache_key()))
+    }
 
-    Ok(base.join("plantuml-rs").join(VERSION))
+    // The temp directory is already per-user on Windows
+    #[cfg(not(unix))]
+    Ok(std::env::temp_dir().join("plantuml-rs").join(cache_key()))
+}
+
+/// Create `dir` with mode 0700, or check that an existing one is private to `uid`.
+#[cfg(unix)]
+fn private_dir(dir: &Path, uid: u32) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => {
+            return Err(PlantUmlError::BinaryExtraction(e))
+        }
+        _ => {}
+    }
+
+    // Don't follow symlinks: a link to someone else's directory is not ours
+    let metadata = fs::symlink_metadata(dir).map_err(PlantUmlError::BinaryExtraction)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(PlantUmlError::BinaryExtraction(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "'{}' is not a private directory owned by the current user",
+                dir.display()
+            ),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Name of the cache subdirectory for the embedded bundle.
+///
+/// Keyed by bundle content rather than crate version, so releases that ship
+/// the same JRE and JAR share one extraction.
+fn cache_key() -> String {
+    format!("bundle-{}", BUNDLE_HASH)
 }
 
 #[cfg(test)]
@@ -130,4 +478,119 @@ mod tests {
             "Bundle should be at least 10MB (JRE + JAR)"
         );
     }
+
+    #[test]
+    fn test_pre_extracted_bundle_missing() {
+        let config = BundleConfig {
+            pre_extracted: Some(std::env::temp_dir().join("plantuml-rs-no-such-bundle")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            locate_bundle(&config),
+            Err(PlantUmlError::BinaryExtraction(_))
+        ));
+    }
+
+    #[test]
+    fn test_extract_to_configured_dir() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-extract-{}", std::process::id()));
+        let config = BundleConfig {
+            cache_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let located = locate_bundle(&config).unwrap();
+        assert_eq!(located, dir);
+        assert!(is_extracted(&dir));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_verify_jar() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-verify-{}", std::process::id()));
+        let paths = extract_bundle_to(&dir).unwrap();
+        assert!(verify_jar(&paths.plantuml_jar).is_ok());
+
+        fs::write(&paths.plantuml_jar, b"not the real jar").unwrap();
+        assert!(matches!(
+            verify_jar(&paths.plantuml_jar),
+            Err(PlantUmlError::JarChecksumMismatch { .. })
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_extraction_keeps_existing_files() {
+        use zip::write::SimpleFileOptions;
+
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-failed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), b"unrelated").unwrap();
+
+        // `jre/bin` as a file makes creating `jre/bin/java` fail mid-extraction
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["plantuml.jar", "jre/bin", "jre/bin/java"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+        }
+        let mut archive = zip::ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        assert!(extract_from(&mut archive, &dir).is_err());
+        assert_eq!(fs::read(dir.join("notes.txt")).unwrap(), b"unrelated");
+        assert_eq!(
+            fs::read_dir(&dir).unwrap().count(),
+            1,
+            "Staging should be removed"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bundle_central_directory() {
+        let archive = open_bundle();
+        assert!(
+            archive.is_ok(),
+            "Bundle should be a valid ZIP: {:?}",
+            archive.err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_dir_rejects_shared_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // SAFETY: getuid has no preconditions and cannot fail
+        let uid = unsafe { libc::getuid() };
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-private-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(private_dir(&dir, uid).is_ok());
+        assert_eq!(
+            fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(matches!(
+            private_dir(&dir, uid),
+            Err(PlantUmlError::BinaryExtraction(_))
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cache_key_is_content_hash() {
+        let key = cache_key();
+        assert_eq!(key.len(), "bundle-".len() + 16, "Key: {}", key);
+        assert!(
+            !key.contains(VERSION),
+            "Key should not depend on crate version"
+        );
+    }
 }
@@ -0,0 +1,109 @@
+//! Helpers for rendering diagrams from a Cargo build script.
+//!
+//! # Example
+//!
+//! ```no_run
+//! // In build.rs: renders docs/diagrams/**/*.puml into $OUT_DIR/diagrams
+//! plantuml::build::render_dir_in_build_script("docs/diagrams", "diagrams").unwrap();
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::batch::{render_dir, source_dependencies, BatchOptions, FileStatus};
+use crate::error::Result;
+use crate::input::read_source;
+
+/// Render every diagram under `src` to SVG files under `out`.
+///
+/// Intended to be called from `build.rs`. Rendering is done by
+/// [`render_dir`] with the default [`BatchOptions`], so:
+///
+/// * A relative `out` is placed inside `$OUT_DIR`, mirroring the layout of `src`.
+/// * Relative `!include` targets resolve against each source's directory.
+/// * Outputs newer than their source and all of its includes are not re-rendered.
+///
+/// `cargo:rerun-if-changed` is emitted for `src`, every source and every
+/// file they include.
+///
+/// # Returns
+///
+/// The paths of all output files, rendered or cached, or the first error
+/// once every other file has been rendered.
+pub fn render_dir_in_build_script(
+    src: impl AsRef<Path>,
+    out: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>> {
+    let src = src.as_ref();
+    let out = match std::env::var_os("OUT_DIR") {
+        Some(out_dir) if out.as_ref().is_relative() => Path::new(&out_dir).join(out),
+        _ => out.as_ref().to_path_buf(),
+    };
+
+    println!("cargo:rerun-if-changed={}", src.display());
+
+    let report = render_dir(src, &out, &BatchOptions::default())?;
+
+    let mut watched = BTreeSet::new();
+    for file in &report.files {
+        watched.insert(file.source.clone());
+        // A source that can't be read or traced has failed the batch already
+        if let Ok(deps) = read_source(&file.source)
+            .and_then(|plantuml| source_dependencies(&file.source, &plantuml))
+        {
+            watched.extend(deps);
+        }
+    }
+    for path in &watched {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
+    let mut outputs = Vec::with_capacity(report.files.len());
+    for file in report.files {
+        if let FileStatus::Failed(e) = file.status {
+            return Err(e);
+        }
+        outputs.push(file.output);
+    }
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::is_fresh;
+    use std::fs;
+
+    #[test]
+    fn test_render_dir_in_build_script() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-build-{}", std::process::id()));
+        let src = dir.join("src");
+        let out = dir.join("out");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(
+            src.join("a.puml"),
+            "@startuml\n!include common.iuml\nA -> B\n@enduml\n",
+        )
+        .unwrap();
+        fs::write(src.join("common.iuml"), "skinparam monochrome true\n").unwrap();
+        fs::write(
+            src.join("nested").join("b.plantuml"),
+            "@startuml\nB -> C\n@enduml\n",
+        )
+        .unwrap();
+
+        let outputs = render_dir_in_build_script(&src, &out).unwrap();
+        assert_eq!(
+            outputs,
+            vec![out.join("a.svg"), out.join("nested").join("b.svg")]
+        );
+        assert!(outputs.iter().all(|p| p.exists()));
+        assert!(is_fresh(
+            &out.join("a.svg"),
+            [&src.join("a.puml"), &src.join("common.iuml")]
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -6,11 +6,68 @@ pub enum PlantUmlError {
     #[error("failed to extract plantuml binary: {0}")]
     BinaryExtraction(#[source] std::io::Error),
 
+    #[error("plantuml bundle is already initialized; configure it before the first render")]
+    BundleAlreadyInitialized,
+
+    #[error(
+        "embedded plantuml bundle is corrupted (plantuml-rs {version}, target {target}): {reason}"
+    )]
+    BundleCorrupted {
+        version: &'static str,
+        target: &'static str,
+        reason: String,
+    },
+
+    #[error(
+        "not enough disk space to extract plantuml bundle to '{path}': {required} bytes required, {} available",
+        available.map_or_else(|| "unknown".to_string(), |b| format!("{b} bytes"))
+    )]
+    CacheDiskFull {
+        path: PathBuf,
+        required: u64,
+        available: Option<u64>,
+    },
+
+    #[error("plantuml jar '{path}' does not match the bundled jar (expected sha256 {expected}, got {actual})")]
+    JarChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("include '{0}' is not within the allowed include directories")]
+    IncludeNotAllowed(String),
+
+    #[error("unsupported diagram type {kind} at line {line}")]
+    UnsupportedDiagram { kind: String, line: usize },
+
+    #[error("diagram source exceeds the limit of {limit} {measure}")]
+    TooComplex { measure: &'static str, limit: usize },
+
+    #[error("CPU time and file size limits are only supported on Unix")]
+    ResourceLimitsUnsupported,
+
+    #[error("unknown output format '{0}'")]
+    UnknownFormat(String),
+
+    #[error("invalid value '{value}' for environment variable {name}: {reason}")]
+    InvalidEnvVar {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("invalid charset name '{0}'")]
+    InvalidCharset(String),
+
     #[error("plantuml process failed with exit code {code}: {stderr}")]
     ProcessFailed { code: i32, stderr: String },
 
-    #[error("plantuml process terminated by signal")]
-    ProcessSignaled,
+    #[error("plantuml process terminated by signal {signal}: {stderr}")]
+    ProcessSignaled { signal: i32, stderr: String },
+
+    #[error("plantuml process timed out after {0:?}")]
+    Timeout(std::time::Duration),
 
     #[error("failed to spawn plantuml process: {0}")]
     ProcessSpawn(#[source] std::io::Error),
@@ -18,6 +75,12 @@ pub enum PlantUmlError {
     #[error("failed to write to plantuml stdin: {0}")]
     StdinWrite(#[source] std::io::Error),
 
+    #[error("plantuml closed its input after {written} of {total} bytes")]
+    StdinClosed { written: usize, total: usize },
+
+    #[error("failed to read diagram archive: {0}")]
+    ArchiveRead(#[source] std::io::Error),
+
     #[error("failed to read input file '{path}': {source}")]
     InputRead {
         path: PathBuf,
@@ -32,6 +95,19 @@ pub enum PlantUmlError {
         source: std::io::Error,
     },
 
+    #[error("'{first}' and '{second}' would both be rendered to '{output}'")]
+    OutputCollision {
+        output: PathBuf,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("failed to write plantuml output: {0}")]
+    WriterFailed(#[source] std::io::Error),
+
+    #[error("plantuml output exceeded the limit of {limit} bytes")]
+    OutputTooLarge { limit: usize },
+
     #[error("invalid UTF-8 in plantuml output: {0}")]
     InvalidUtf8(#[source] std::string::FromUtf8Error),
 
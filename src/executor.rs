@@ -1,55 +1,729 @@
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::io::{ErrorKind, Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::binary::get_bundle_paths;
 use crate::error::{PlantUmlError, Result};
+use crate::format::OutputFormat;
+use crate::includes::check_allowed;
+use crate::inspect::inspect;
+use crate::options::RenderOptions;
+
+/// Default maximum number of bytes accepted from PlantUML's stdout
+pub const MAX_STDOUT_BYTES: usize = 256 * 1024 * 1024;
+
+/// Default maximum number of bytes of PlantUML's stderr kept for diagnostics
+pub const MAX_STDERR_BYTES: usize = 1024 * 1024;
+
+/// Prefixes of stderr lines the JVM prints for reasons unrelated to the diagram
+const BENIGN_STDERR_PREFIXES: &[&str] = &[
+    "WARNING: An illegal reflective access operation has occurred",
+    "WARNING: Illegal reflective access by",
+    "WARNING: Please consider reporting this to the maintainers",
+    "WARNING: Use --illegal-access=warn",
+    "WARNING: All illegal access operations will be denied",
+    "WARNING: A terminally deprecated method",
+    "WARNING: System::setSecurityManager",
+    "WARNING: sun.misc.Unsafe",
+    "Picked up _JAVA_OPTIONS:",
+    "Picked up JAVA_TOOL_OPTIONS:",
+    "Picked up JDK_JAVA_OPTIONS:",
+    "Fontconfig warning:",
+    "Fontconfig error:",
+];
+
+/// How often a render with a timeout checks whether PlantUML has finished
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// Execute PlantUML with input from stdin and return SVG output.
 ///
 /// Uses PlantUML's `-pipe` mode for efficient stdin/stdout processing.
 pub fn execute_pipe(input: &str) -> Result<String> {
+    let svg = execute_pipe_bytes(input, OutputFormat::Svg)?;
+
+    String::from_utf8(svg).map_err(PlantUmlError::InvalidUtf8)
+}
+
+/// Execute PlantUML with input from stdin and return the raw output bytes.
+pub fn execute_pipe_bytes(input: &str, format: OutputFormat) -> Result<Vec<u8>> {
+    execute(input.as_bytes(), &RenderOptions::new().format(format))
+}
+
+/// Execute PlantUML with the given options and return the raw output bytes.
+///
+/// Output and stderr are capped according to
+/// [`RenderOptions::max_stdout_bytes`] and [`RenderOptions::max_stderr_bytes`]
+/// so a runaway diagram can't exhaust the host's memory.
+///
+/// Sources with a diagram type the bundled PlantUML doesn't know are
+/// rejected before starting the JVM. If GraphViz turns out to be missing,
+/// the render is retried once with the smetana layout engine unless the
+/// fallback is disabled or the source is raw GraphViz (`@startdot`).
+pub fn execute(input: &[u8], options: &RenderOptions) -> Result<Vec<u8>> {
+    execute_with_stderr(input, options).map(|result| result.output)
+}
+
+/// Like [`execute`], but also return PlantUML's stderr from the final attempt.
+pub fn execute_with_stderr(input: &[u8], options: &RenderOptions) -> Result<RenderResult> {
+    check_input(input, options)?;
+
+    let result = execute_with_retry(input, options);
+    let missing = is_graphviz_missing(result.as_ref().map(|r| r.output.as_slice()));
+    if options.smetana || options.no_graphviz_fallback || !missing || !smetana_can_layout(input) {
+        return result;
+    }
+
+    tracing::warn!("graphviz not found, retrying with the smetana layout engine");
+    execute_with_retry(input, &options.clone().smetana(true))
+}
+
+/// Validate the options and check the input's diagram types, its size
+/// against the source limits and, if includes are restricted, its includes.
+pub(crate) fn check_input(input: &[u8], options: &RenderOptions) -> Result<()> {
+    options.validate()?;
+
+    let source = String::from_utf8_lossy(input);
+    if let Some(diagram) = inspect(&source)
+        .diagrams
+        .into_iter()
+        .find(|d| !d.kind.is_supported())
+    {
+        return Err(PlantUmlError::UnsupportedDiagram {
+            kind: diagram.kind.to_string(),
+            line: diagram.start_line,
+        });
+    }
+
+    if let Some(limit) = options
+        .max_source_bytes
+        .filter(|&limit| input.len() > limit)
+    {
+        return Err(PlantUmlError::TooComplex {
+            measure: "bytes",
+            limit,
+        });
+    }
+    if let Some(limit) = options
+        .max_source_lines
+        .filter(|&limit| line_count(input) > limit)
+    {
+        return Err(PlantUmlError::TooComplex {
+            measure: "lines",
+            limit,
+        });
+    }
+
+    if let Some(allowed) = &options.include_allowlist {
+        check_allowed(&source, &options.include_options(), allowed)?;
+    }
+
+    Ok(())
+}
+
+/// Whether re-rendering with smetana can help when GraphViz is missing, i.e.
+/// no diagram is raw GraphViz input, which only `dot` itself can render.
+pub(crate) fn smetana_can_layout(input: &[u8]) -> bool {
+    let source = String::from_utf8_lossy(input);
+
+    !inspect(&source)
+        .diagrams
+        .iter()
+        .any(|d| d.kind.requires_graphviz())
+}
+
+/// Number of lines in `input`, counting a final line without a newline.
+fn line_count(input: &[u8]) -> usize {
+    let newlines = input.iter().filter(|&&b| b == b'\n').count();
+
+    match input.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+/// Run PlantUML, retrying transient failures according to the options' retry policy.
+///
+/// The delay starts at the configured backoff and doubles after every attempt.
+fn execute_with_retry(input: &[u8], options: &RenderOptions) -> Result<RenderResult> {
+    let mut delay = options.retry_backoff;
+    let mut attempt = 0;
+
+    loop {
+        match execute_once(Input::Bytes(input), options) {
+            Err(e) if attempt < options.retries && is_transient(&e) => {
+                attempt += 1;
+                tracing::warn!(error = %e, attempt, "plantuml failed, retrying");
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether an error may go away on its own, e.g. a spawn blocked by an
+/// antivirus scan of the freshly extracted JRE.
+///
+/// A JVM that exits cleanly before reading the whole diagram is retried as
+/// well. Permanent failures such as a missing `java` are not, nor are
+/// failures PlantUML reports itself.
+pub(crate) fn is_transient(error: &PlantUmlError) -> bool {
+    match error {
+        PlantUmlError::ProcessSpawn(e) => matches!(
+            e.kind(),
+            ErrorKind::WouldBlock
+                | ErrorKind::Interrupted
+                | ErrorKind::ResourceBusy
+                | ErrorKind::ExecutableFileBusy
+        ),
+        PlantUmlError::StdinClosed { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether a render failed because PlantUML couldn't find GraphViz `dot`.
+///
+/// PlantUML reports this either on stderr or as an error diagram.
+pub(crate) fn is_graphviz_missing(result: std::result::Result<&[u8], &PlantUmlError>) -> bool {
+    const MESSAGE: &[u8] = b"Cannot find Graphviz";
+
+    match result {
+        Ok(output) => output.windows(MESSAGE.len()).any(|w| w == MESSAGE),
+        Err(PlantUmlError::SyntaxError(stderr) | PlantUmlError::ProcessFailed { stderr, .. }) => {
+            stderr.contains("Cannot find Graphviz")
+        }
+        Err(_) => false,
+    }
+}
+
+/// Build the command that runs PlantUML with piped stdio for `options`.
+pub(crate) fn command(options: &RenderOptions) -> Result<Command> {
     let paths = get_bundle_paths()?;
 
-    let mut child = Command::new(&paths.java_exe)
-        .args([
-            "-jar",
-            paths.plantuml_jar.to_str().unwrap(),
-            "-pipe",
-            "-tsvg",
-            "-charset",
-            "UTF-8",
-        ])
+    let mut command = Command::new(&paths.java_exe);
+    if options.env_clear {
+        command.env_clear();
+    }
+    command.envs(options.env.iter().map(|(k, v)| (k, v)));
+    command.args(options.jvm_args());
+    command.args(["-jar", paths.plantuml_jar.to_str().unwrap()]);
+    command.args(options.plantuml_args());
+    #[cfg(unix)]
+    apply_limits(&mut command, &options.limits);
+    if let Some(increment) = options.nice {
+        apply_nice(&mut command, increment);
+    }
+
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(PlantUmlError::ProcessSpawn)?;
+        .stderr(Stdio::piped());
+
+    Ok(command)
+}
+
+/// Execute PlantUML with input streamed from `reader` and return the raw output bytes.
+///
+/// The reader is copied to PlantUML's stdin on a separate thread, so the
+/// timeout covers a slow reader or a JVM that stops reading as well; on
+/// timeout that thread is left to finish on its own. A reader can't be
+/// replayed, so renders are never retried or re-run with smetana, except
+/// when includes are restricted or the source is limited: then the input is
+/// buffered, up to one byte past [`RenderOptions::max_source_bytes`], to
+/// check it first and rendered like [`execute`].
+pub fn execute_from_reader(
+    reader: Box<dyn Read + Send>,
+    options: &RenderOptions,
+) -> Result<Vec<u8>> {
+    if options.inspects_source() {
+        let limit = options
+            .max_source_bytes
+            .map_or(u64::MAX, |limit| limit as u64 + 1);
+        let mut input = Vec::new();
+        reader
+            .take(limit)
+            .read_to_end(&mut input)
+            .map_err(reader_error)?;
+        return execute(&input, options);
+    }
+
+    options.validate()?;
+    execute_once(Input::Reader(reader), options).map(|result| result.output)
+}
+
+/// Output of a render together with what PlantUML printed on stderr.
+///
+/// Returned by [`render_with_stderr`](crate::render_with_stderr) for
+/// debugging renders that succeed but look wrong.
+#[derive(Debug, Clone)]
+pub struct RenderResult {
+    /// The rendered diagram in the requested format.
+    pub output: Vec<u8>,
+    /// PlantUML's stderr exactly as captured, warnings and JVM noise
+    /// included even when [`RenderOptions::quiet`] is set. Capped at
+    /// [`RenderOptions::max_stderr_bytes`].
+    pub raw_stderr: String,
+}
+
+/// Source fed to PlantUML's stdin from a separate thread.
+enum Input<'a> {
+    Bytes(&'a [u8]),
+    Reader(Box<dyn Read + Send>),
+}
+
+/// Spawn PlantUML once and collect its output.
+fn execute_once(input: Input<'_>, options: &RenderOptions) -> Result<RenderResult> {
+    let mut command = command(options)?;
+
+    tracing::debug!(command = ?command, "spawning plantuml");
+
+    let child = command.spawn().map_err(PlantUmlError::ProcessSpawn)?;
+    let mut child = ChildGuard::new(child, !options.leave_running);
+
+    // Drain stdout and stderr concurrently so the JVM never blocks on a full pipe
+    let stdout = child
+        .stdout
+        .take()
+        .map(|stdout| read_stdout(stdout, options.stdout_limit()));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| read_stderr(stderr, options.stderr_limit()));
+
+    // Feed stdin from its own thread too, so a JVM that stops reading
+    // can't block us past the timeout
+    let stdin = match (child.stdin.take(), input) {
+        (Some(stdin), Input::Bytes(input)) => Some(write_stdin(stdin, input.to_vec())),
+        (Some(stdin), Input::Reader(reader)) => Some(copy_stdin(stdin, reader)),
+        (None, _) => None,
+    };
+
+    // stdout closes when PlantUML exits (or the output cap is hit)
+    if let (Some(timeout), Some(handle)) = (options.timeout, &stdout) {
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                child.kill_on_drop = true;
+                return Err(PlantUmlError::Timeout(timeout));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    let stdout = stdout.map(|h| h.join().expect("stdout reader panicked"));
+    let stderr = stderr.map(|h| h.join().expect("stderr reader panicked"));
+
+    let stdout = match stdout.transpose() {
+        Ok(stdout) => stdout.unwrap_or_default(),
+        Err(e) => {
+            // Stop the process instead of waiting for it to finish writing output we discard
+            child.kill_on_drop = true;
+            return Err(e);
+        }
+    };
+    let stdin = stdin.map(|h| h.join().expect("stdin writer panicked"));
+    // A failing reader explains the truncated input better than PlantUML's complaint about it
+    if let Some(Err(e @ PlantUmlError::InputRead { .. })) = stdin {
+        return Err(e);
+    }
+    let raw_stderr = stderr.unwrap_or_default();
+    let stderr = if options.quiet {
+        strip_jvm_noise(&raw_stderr)
+    } else {
+        raw_stderr.clone()
+    };
+
+    // Wait for process
+    let status = child.wait().map_err(PlantUmlError::ProcessSpawn)?;
+
+    tracing::debug!(
+        status = %status,
+        stdout_bytes = stdout.len(),
+        stderr = %stderr,
+        "plantuml exited"
+    );
+
+    if !status.success() {
+        return Err(exit_error(status, stderr));
+    }
+
+    // A process that exited cleanly without reading all input still lost part of the diagram
+    stdin.transpose()?;
+
+    Ok(RenderResult {
+        output: stdout,
+        raw_stderr,
+    })
+}
+
+/// Execute PlantUML and stream its output into `writer` as it is produced.
+///
+/// Nothing is buffered, so there is no output cap; the timeout is enforced
+/// by a watchdog thread. Since output may already have been written when a
+/// failure is detected, renders are never retried or re-run with smetana.
+/// Returns the number of bytes written.
+pub fn execute_to_writer(
+    input: &[u8],
+    options: &RenderOptions,
+    writer: &mut dyn Write,
+) -> Result<u64> {
+    check_input(input, options)?;
+    let mut command = command(options)?;
+
+    tracing::debug!(command = ?command, "spawning plantuml");
+
+    let child = command.spawn().map_err(PlantUmlError::ProcessSpawn)?;
+    let mut child = ChildGuard::new(child, !options.leave_running);
+
+    let stdout = child.stdout.take();
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| read_stderr(stderr, options.stderr_limit()));
+    let stdin = child
+        .stdin
+        .take()
+        .map(|stdin| write_stdin(stdin, input.to_vec()));
+
+    // The main thread is busy copying, so a watchdog kills the process on timeout
+    let child = Arc::new(Mutex::new(child));
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = options.timeout.map(|timeout| {
+        let child = Arc::clone(&child);
+        thread::spawn(move || {
+            let timed_out = finished.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout);
+            if timed_out {
+                let mut child = child.lock().unwrap();
+                child.kill_on_drop = true;
+                let _ = child.kill();
+            }
+            timed_out
+        })
+    });
+
+    let copied = stdout.map_or(Ok(0), |stdout| copy_output(stdout, writer));
+    drop(done);
+
+    let timed_out = watchdog.is_some_and(|h| h.join().expect("watchdog panicked"));
+    let mut child = child.lock().unwrap();
+    if timed_out {
+        return Err(PlantUmlError::Timeout(options.timeout.unwrap_or_default()));
+    }
+
+    let copied = match copied {
+        Ok(copied) => copied,
+        Err(e) => {
+            // Stop the process instead of waiting for it to finish writing output we discard
+            child.kill_on_drop = true;
+            return Err(e);
+        }
+    };
+    let stderr = stderr.map(|h| h.join().expect("stderr reader panicked"));
+    let stdin = stdin.map(|h| h.join().expect("stdin writer panicked"));
+    let mut stderr = stderr.unwrap_or_default();
+    if options.quiet {
+        stderr = strip_jvm_noise(&stderr);
+    }
+
+    let status = child.wait().map_err(PlantUmlError::ProcessSpawn)?;
+
+    tracing::debug!(
+        status = %status,
+        stdout_bytes = copied,
+        stderr = %stderr,
+        "plantuml exited"
+    );
+
+    if !status.success() {
+        return Err(exit_error(status, stderr));
+    }
+
+    stdin.transpose()?;
+
+    Ok(copied)
+}
+
+/// Copy PlantUML's stdout into `writer`, telling read and write failures apart.
+fn copy_output(mut stdout: ChildStdout, writer: &mut dyn Write) -> Result<u64> {
+    let mut buf = [0u8; 64 * 1024];
+    let mut copied = 0;
+
+    loop {
+        let n = match stdout.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(PlantUmlError::ProcessSpawn(e)),
+        };
+        writer
+            .write_all(&buf[..n])
+            .map_err(PlantUmlError::WriterFailed)?;
+        copied += n as u64;
+    }
+
+    writer.flush().map_err(PlantUmlError::WriterFailed)?;
+
+    Ok(copied)
+}
+
+/// Set the CPU time and file size rlimits in the child before it execs java.
+#[cfg(unix)]
+fn apply_limits(command: &mut Command, limits: &crate::ResourceLimits) {
+    use std::os::unix::process::CommandExt;
+
+    let cpu = limits
+        .cpu_time
+        .map(|t| t.as_secs() + u64::from(t.subsec_nanos() > 0));
+    let file_size = limits.file_size;
+    if cpu.is_none() && file_size.is_none() {
+        return;
+    }
+
+    let set = |resource, value: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        // SAFETY: setrlimit is async-signal-safe and only reads `limit`
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    };
+
+    // SAFETY: the closure only calls setrlimit, which is safe between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(cpu) = cpu {
+                set(libc::RLIMIT_CPU, cpu)?;
+            }
+            if let Some(file_size) = file_size {
+                set(libc::RLIMIT_FSIZE, file_size)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Lower the child's priority by `increment` before it execs java.
+#[cfg(unix)]
+fn apply_nice(command: &mut Command, increment: i32) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: nice is async-signal-safe. Lowering priority can't fail for
+    // unprivileged processes, and a failure wouldn't warrant aborting the render.
+    unsafe {
+        command.pre_exec(move || {
+            libc::nice(increment);
+            Ok(())
+        });
+    }
+}
+
+/// Start the child in a lower priority class.
+#[cfg(windows)]
+fn apply_nice(command: &mut Command, increment: i32) {
+    use std::os::windows::process::CommandExt;
+
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+
+    match increment {
+        i if i >= 15 => command.creation_flags(IDLE_PRIORITY_CLASS),
+        i if i > 0 => command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS),
+        _ => command,
+    };
+}
 
-    // Write input to stdin
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(input.as_bytes())
-            .map_err(PlantUmlError::StdinWrite)?;
+#[cfg(not(any(unix, windows)))]
+fn apply_nice(_command: &mut Command, _increment: i32) {}
+
+/// Map an unsuccessful exit status to the matching error.
+pub(crate) fn exit_error(status: ExitStatus, stderr: String) -> PlantUmlError {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return PlantUmlError::ProcessSignaled { signal, stderr };
+        }
+    }
+
+    // Check if it's a syntax error
+    if stderr.contains("Syntax Error") || stderr.contains("@startuml") {
+        return PlantUmlError::SyntaxError(stderr);
+    }
+
+    PlantUmlError::ProcessFailed {
+        code: status.code().unwrap_or(-1),
+        stderr,
     }
+}
+
+/// Write `input` to stdin on a separate thread, closing it when done.
+///
+/// If PlantUML closes its end early, the result reports how much was written.
+fn write_stdin(mut stdin: ChildStdin, input: Vec<u8>) -> thread::JoinHandle<Result<()>> {
+    thread::spawn(move || {
+        let mut written = 0;
+
+        while written < input.len() {
+            match stdin.write(&input[written..]) {
+                Ok(0) => break,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(PlantUmlError::StdinWrite(e)),
+            }
+        }
+
+        if written < input.len() {
+            return Err(PlantUmlError::StdinClosed {
+                written,
+                total: input.len(),
+            });
+        }
+
+        Ok(())
+    })
+}
 
-    // Wait for process and collect output
-    let output = child
-        .wait_with_output()
-        .map_err(PlantUmlError::ProcessSpawn)?;
+/// Copy `reader` to stdin on a separate thread, closing it when done.
+///
+/// If PlantUML closes its end early, the copy stops and its exit status
+/// explains why.
+fn copy_stdin(
+    mut stdin: ChildStdin,
+    mut reader: Box<dyn Read + Send>,
+) -> thread::JoinHandle<Result<()>> {
+    thread::spawn(move || {
+        let mut buf = [0; 64 * 1024];
+
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(reader_error(e)),
+            };
+
+            match stdin.write_all(&buf[..n]) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+                Err(e) => return Err(PlantUmlError::StdinWrite(e)),
+            }
+        }
+    })
+}
+
+/// Error for a source reader that failed, which has no path of its own.
+fn reader_error(source: std::io::Error) -> PlantUmlError {
+    PlantUmlError::InputRead {
+        path: PathBuf::from("<reader>"),
+        source,
+    }
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let code = output.status.code().unwrap_or(-1);
+/// Kills and reaps the PlantUML process when dropped, e.g. on an early
+/// return or when the calling thread panics, so no JVM outlives its render.
+pub(crate) struct ChildGuard {
+    child: Child,
+    kill_on_drop: bool,
+    exited: bool,
+}
 
-        // Check if it's a syntax error
-        if stderr.contains("Syntax Error") || stderr.contains("@startuml") {
-            return Err(PlantUmlError::SyntaxError(stderr));
+impl ChildGuard {
+    pub(crate) fn new(child: Child, kill_on_drop: bool) -> Self {
+        Self {
+            child,
+            kill_on_drop,
+            exited: false,
         }
+    }
+
+    pub(crate) fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        self.exited = true;
+        Ok(status)
+    }
+}
+
+impl Deref for ChildGuard {
+    type Target = Child;
 
-        return Err(PlantUmlError::ProcessFailed { code, stderr });
+    fn deref(&self) -> &Child {
+        &self.child
     }
+}
 
-    String::from_utf8(output.stdout).map_err(PlantUmlError::InvalidUtf8)
+impl DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if self.kill_on_drop && !self.exited {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Remove known-benign JVM warnings from PlantUML's stderr, keeping everything else.
+pub(crate) fn strip_jvm_noise(stderr: &str) -> String {
+    stderr
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !BENIGN_STDERR_PREFIXES.iter().any(|p| line.starts_with(p))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Read stdout on a separate thread, failing once it exceeds `limit` bytes.
+fn read_stdout(stdout: ChildStdout, limit: usize) -> thread::JoinHandle<Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        stdout
+            .take(limit as u64 + 1)
+            .read_to_end(&mut buf)
+            .map_err(PlantUmlError::ProcessSpawn)?;
+
+        if buf.len() > limit {
+            return Err(PlantUmlError::OutputTooLarge { limit });
+        }
+
+        Ok(buf)
+    })
+}
+
+/// Read stderr on a separate thread, keeping at most `limit` bytes.
+///
+/// The remainder is still drained so the process can't block on it, and a
+/// truncation marker is appended.
+pub(crate) fn read_stderr(mut stderr: ChildStderr, limit: usize) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = (&mut stderr).take(limit as u64).read_to_end(&mut buf);
+        let dropped = std::io::copy(&mut stderr, &mut std::io::sink()).unwrap_or(0);
+
+        let mut text = String::from_utf8_lossy(&buf).into_owned();
+        if dropped > 0 {
+            text.push_str(&format!(
+                "\n[plantuml-rs: stderr truncated, {} more bytes]",
+                dropped
+            ));
+        }
+        text
+    })
 }
 
 #[cfg(test)]
@@ -70,6 +744,297 @@ Alice -> Bob: Hello
         assert!(svg.contains("Alice"), "SVG should contain Alice");
     }
 
+    #[test]
+    fn test_png_output() {
+        let input = "@startuml\nAlice -> Bob: Hello\n@enduml";
+
+        let result = execute_pipe_bytes(input, OutputFormat::Png);
+        assert!(result.is_ok(), "Should render PNG: {:?}", result);
+        assert!(
+            result.unwrap().starts_with(b"\x89PNG\r\n\x1a\n"),
+            "Output should be a PNG"
+        );
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let input = b"@startuml\nAlice -> Bob: Hello\n@enduml";
+        let options = RenderOptions::new().max_stdout_bytes(16);
+        assert!(matches!(
+            execute(input, &options),
+            Err(PlantUmlError::OutputTooLarge { limit: 16 })
+        ));
+
+        let options = RenderOptions::new().max_stderr_bytes(5);
+        match execute(b"not a diagram", &options) {
+            Err(
+                PlantUmlError::SyntaxError(stderr) | PlantUmlError::ProcessFailed { stderr, .. },
+            ) => {
+                assert!(stderr.contains("stderr truncated"), "stderr: {}", stderr)
+            }
+            other => panic!("Expected a failed render, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_diagram() {
+        let source = b"@startuml\nA -> B\n@enduml\n\n@startbogus\nfoo\n@endbogus\n";
+        let result = execute(source, &RenderOptions::new());
+        assert!(
+            matches!(
+                &result,
+                Err(PlantUmlError::UnsupportedDiagram { kind, line: 5 }) if kind == "@startbogus"
+            ),
+            "Unknown diagram type should be rejected: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_smetana_can_layout() {
+        assert!(smetana_can_layout(b"@startuml\nclass A\n@enduml"));
+        assert!(smetana_can_layout(b"A -> B"));
+        assert!(!smetana_can_layout(
+            b"@startdot\ndigraph { a -> b }\n@enddot"
+        ));
+    }
+
+    #[test]
+    fn test_source_limits() {
+        let input = b"@startuml\nA -> B\nB -> C\n@enduml\n";
+
+        let options = RenderOptions::new().max_source_bytes(10);
+        assert!(matches!(
+            check_input(input, &options),
+            Err(PlantUmlError::TooComplex {
+                measure: "bytes",
+                limit: 10
+            })
+        ));
+        let options = RenderOptions::new().max_source_lines(3);
+        assert!(matches!(
+            check_input(input, &options),
+            Err(PlantUmlError::TooComplex {
+                measure: "lines",
+                limit: 3
+            })
+        ));
+        let options = RenderOptions::new()
+            .max_source_bytes(input.len())
+            .max_source_lines(4);
+        assert!(check_input(input, &options).is_ok());
+
+        // A reader is only buffered up to the limit
+        let reader = Box::new(std::io::repeat(b'A'));
+        let options = RenderOptions::new().max_source_bytes(1024);
+        assert!(matches!(
+            execute_from_reader(reader, &options),
+            Err(PlantUmlError::TooComplex { .. })
+        ));
+    }
+
+    #[test]
+    fn test_line_count() {
+        assert_eq!(line_count(b""), 0);
+        assert_eq!(line_count(b"a"), 1);
+        assert_eq!(line_count(b"a\n"), 1);
+        assert_eq!(line_count(b"a\nb"), 2);
+    }
+
+    #[test]
+    fn test_large_diagram_does_not_deadlock() {
+        // Far larger than any pipe buffer, so stdin is still being written
+        // while PlantUML is already producing output
+        let mut input = String::from("@startuml\n");
+        for i in 0..100_000 {
+            input.push_str(&format!("Alice -> Bob: message {}\n", i));
+        }
+        input.push_str("@enduml\n");
+
+        let result = execute_pipe(&input);
+        assert!(
+            result.is_ok(),
+            "Large diagram should render: {:?}",
+            result.map(|s| s.len())
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_child_guard_kills_on_drop() {
+        let paths = get_bundle_paths().unwrap();
+        // Waits for input on stdin, which is never closed
+        let child = Command::new(&paths.java_exe)
+            .args(["-jar", paths.plantuml_jar.to_str().unwrap(), "-pipe"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        drop(ChildGuard::new(child, true));
+
+        assert!(
+            !std::path::Path::new(&format!("/proc/{}", pid)).exists(),
+            "Process should be killed and reaped"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_error_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Raw wait status of a process killed by SIGKILL
+        let error = exit_error(ExitStatus::from_raw(9), String::new());
+        assert!(
+            matches!(error, PlantUmlError::ProcessSignaled { signal: 9, .. }),
+            "Unexpected error: {:?}",
+            error
+        );
+
+        let error = exit_error(ExitStatus::from_raw(200 << 8), "Syntax Error?".to_string());
+        assert!(matches!(error, PlantUmlError::SyntaxError(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_render_with_limits() {
+        let options = RenderOptions::new().limits(crate::ResourceLimits {
+            cpu_time: Some(Duration::from_secs(60)),
+            file_size: Some(16 * 1024 * 1024),
+            ..Default::default()
+        });
+
+        let result = execute(b"@startuml\nAlice -> Bob\n@enduml", &options);
+        assert!(result.is_ok(), "Should render within limits: {:?}", result);
+    }
+
+    #[test]
+    fn test_render_with_nice() {
+        let options = RenderOptions::new().nice(10);
+        let result = execute(b"@startuml\nAlice -> Bob\n@enduml", &options);
+        assert!(
+            result.is_ok(),
+            "Should render at low priority: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_render_with_cleared_env() {
+        let options = RenderOptions::new().env_clear(true).env("LANG", "C.UTF-8");
+        let result = execute(b"@startuml\nAlice -> Bob\n@enduml", &options);
+        assert!(
+            result.is_ok(),
+            "Should render without inherited env: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_execute_to_writer() {
+        let mut out = Vec::new();
+        let options = RenderOptions::new().format(OutputFormat::Png);
+
+        let copied = execute_to_writer(b"@startuml\nAlice -> Bob\n@enduml", &options, &mut out);
+        assert_eq!(copied.ok(), Some(out.len() as u64));
+        assert!(out.starts_with(b"\x89PNG"), "Output should be a PNG");
+    }
+
+    #[test]
+    fn test_execute_from_reader() {
+        let reader = std::io::Cursor::new(b"@startuml\nAlice -> Bob\n@enduml\n".to_vec());
+
+        let svg = execute_from_reader(Box::new(reader), &RenderOptions::new());
+        assert!(svg.is_ok(), "Should render from reader: {:?}", svg);
+        assert!(String::from_utf8(svg.unwrap()).unwrap().contains("Alice"));
+    }
+
+    #[test]
+    fn test_execute_from_reader_failures() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+        let result = execute_from_reader(Box::new(Failing), &RenderOptions::new());
+        assert!(matches!(result, Err(PlantUmlError::InputRead { .. })));
+
+        // A reader that never returns must not outlive the timeout
+        struct Stalled;
+        impl Read for Stalled {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                thread::sleep(Duration::from_secs(3600));
+                Ok(0)
+            }
+        }
+        let options = RenderOptions::new().timeout(Duration::from_secs(2));
+        let result = execute_from_reader(Box::new(Stalled), &options);
+        assert!(matches!(result, Err(PlantUmlError::Timeout(_))));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let busy = PlantUmlError::ProcessSpawn(ErrorKind::ExecutableFileBusy.into());
+        assert!(is_transient(&busy));
+        let closed = PlantUmlError::StdinClosed {
+            written: 1,
+            total: 2,
+        };
+        assert!(is_transient(&closed));
+
+        let missing_java = PlantUmlError::ProcessSpawn(ErrorKind::NotFound.into());
+        assert!(!is_transient(&missing_java));
+        assert!(!is_transient(&PlantUmlError::SyntaxError(String::new())));
+    }
+
+    #[test]
+    fn test_retry_on_closed_stdin() {
+        // `java -version` exits cleanly without reading the diagram, which
+        // is too large to fit in the pipe buffer
+        let source = format!("@startuml\n{}@enduml", "A -> B\n".repeat(128 * 1024));
+        let options = RenderOptions::new().jvm_arg("-version");
+        let backoff = Duration::from_millis(200);
+
+        let started = Instant::now();
+        let result = execute(source.as_bytes(), &options);
+        let once = started.elapsed();
+        assert!(
+            matches!(result, Err(PlantUmlError::StdinClosed { .. })),
+            "Unread input should fail: {:?}",
+            result
+        );
+
+        let started = Instant::now();
+        let result = execute(source.as_bytes(), &options.retry(2, backoff));
+        assert!(matches!(result, Err(PlantUmlError::StdinClosed { .. })));
+        // Both retries waited out their backoff
+        assert!(started.elapsed() >= once + backoff * 3);
+    }
+
+    #[test]
+    fn test_is_graphviz_missing() {
+        let error_svg = b"<svg><text>Cannot find Graphviz. You should try</text></svg>";
+        assert!(is_graphviz_missing(Ok(&error_svg[..])));
+        assert!(!is_graphviz_missing(Ok(&b"<svg/>"[..])));
+        assert!(is_graphviz_missing(Err(&PlantUmlError::ProcessFailed {
+            code: 1,
+            stderr: "Dot executable does not exist\nCannot find Graphviz".to_string(),
+        })));
+    }
+
+    #[test]
+    fn test_strip_jvm_noise() {
+        let stderr = "Picked up _JAVA_OPTIONS: -Xmx1g\n\
+                      WARNING: An illegal reflective access operation has occurred\n\
+                      ERROR\n\
+                      3\n\
+                      Syntax Error?";
+
+        assert_eq!(strip_jvm_noise(stderr), "ERROR\n3\nSyntax Error?");
+    }
+
     #[test]
     fn test_syntax_error() {
         let input = "this is not valid plantuml";
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::PlantUmlError;
+
+/// Output formats supported by PlantUML, mapped to its `-t*` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// Scalable Vector Graphics (`-tsvg`)
+    #[default]
+    Svg,
+    /// PNG raster image (`-tpng`)
+    Png,
+    /// PDF document (`-tpdf`)
+    Pdf,
+    /// Encapsulated PostScript with text drawn as shapes (`-teps`)
+    Eps,
+    /// Encapsulated PostScript with text kept as text (`-teps:text`)
+    EpsText,
+    /// LaTeX/TikZ with preamble (`-tlatex`)
+    Latex,
+    /// LaTeX/TikZ without preamble (`-tlatex:nopreamble`)
+    LatexNoPreamble,
+    /// ASCII art (`-ttxt`)
+    Txt,
+    /// Unicode ASCII art (`-tutxt`)
+    Utxt,
+    /// Visio VDX (`-tvdx`)
+    Vdx,
+    /// XMI for UML tools (`-txmi`)
+    Xmi,
+    /// SCXML for state diagrams (`-tscxml`)
+    Scxml,
+    /// HTML, for class diagrams (`-thtml`)
+    Html,
+    /// Braille PNG for tactile printing (`-tbraille`)
+    Braille,
+}
+
+impl OutputFormat {
+    const ALL: [Self; 14] = [
+        Self::Svg,
+        Self::Png,
+        Self::Pdf,
+        Self::Eps,
+        Self::EpsText,
+        Self::Latex,
+        Self::LatexNoPreamble,
+        Self::Txt,
+        Self::Utxt,
+        Self::Vdx,
+        Self::Xmi,
+        Self::Scxml,
+        Self::Html,
+        Self::Braille,
+    ];
+
+    /// The PlantUML command-line flag selecting this format.
+    pub fn flag(self) -> &'static str {
+        match self {
+            Self::Svg => "-tsvg",
+            Self::Png => "-tpng",
+            Self::Pdf => "-tpdf",
+            Self::Eps => "-teps",
+            Self::EpsText => "-teps:text",
+            Self::Latex => "-tlatex",
+            Self::LatexNoPreamble => "-tlatex:nopreamble",
+            Self::Txt => "-ttxt",
+            Self::Utxt => "-tutxt",
+            Self::Vdx => "-tvdx",
+            Self::Xmi => "-txmi",
+            Self::Scxml => "-tscxml",
+            Self::Html => "-thtml",
+            Self::Braille => "-tbraille",
+        }
+    }
+
+    /// Conventional file extension for this format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Png | Self::Braille => "png",
+            Self::Pdf => "pdf",
+            Self::Eps | Self::EpsText => "eps",
+            Self::Latex | Self::LatexNoPreamble => "tex",
+            Self::Txt | Self::Utxt => "txt",
+            Self::Vdx => "vdx",
+            Self::Xmi => "xmi",
+            Self::Scxml => "scxml",
+            Self::Html => "html",
+        }
+    }
+
+    /// Whether the output is text (and valid UTF-8) rather than binary data.
+    pub fn is_text(self) -> bool {
+        !matches!(self, Self::Png | Self::Pdf | Self::Braille)
+    }
+}
+
+/// How text is emitted in EPS output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EpsTextMode {
+    /// Glyphs are converted to outlines; renders identically without fonts.
+    #[default]
+    Shapes,
+    /// Text stays selectable and editable, but needs the fonts at print time.
+    Text,
+}
+
+impl From<EpsTextMode> for OutputFormat {
+    fn from(mode: EpsTextMode) -> Self {
+        match mode {
+            EpsTextMode::Shapes => Self::Eps,
+            EpsTextMode::Text => Self::EpsText,
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.flag()[2..])
+    }
+}
+
+/// Parses the names produced by `Display`, e.g. `"png"` or `"eps:text"`,
+/// ignoring case.
+impl FromStr for OutputFormat {
+    type Err = PlantUmlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| PlantUmlError::UnknownFormat(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_flags() {
+        assert_eq!(OutputFormat::default().flag(), "-tsvg");
+        assert_eq!(OutputFormat::LatexNoPreamble.flag(), "-tlatex:nopreamble");
+        assert_eq!(OutputFormat::Utxt.extension(), "txt");
+        assert_eq!(OutputFormat::Png.to_string(), "png");
+        assert!(!OutputFormat::Pdf.is_text());
+        assert_eq!(OutputFormat::Braille.flag(), "-tbraille");
+        assert_eq!(OutputFormat::Braille.extension(), "png");
+        assert_eq!(OutputFormat::from(EpsTextMode::Text).flag(), "-teps:text");
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        for format in OutputFormat::ALL {
+            assert_eq!(format.to_string().parse::<OutputFormat>().unwrap(), format);
+        }
+        assert_eq!("PNG".parse::<OutputFormat>().unwrap(), OutputFormat::Png);
+        assert!("gif".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{PlantUmlError, Result};
+
+/// Preprocessor directives that pull in another file.
+const INCLUDE_DIRECTIVES: &[&str] = &[
+    "!include_many",
+    "!include_once",
+    "!includesub",
+    "!include",
+    "!import",
+];
+
+/// Where to look for files referenced by `!include` directives.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeOptions {
+    /// Directory relative includes in the top-level source are resolved against.
+    /// Defaults to the current working directory.
+    pub base_dir: Option<PathBuf>,
+    /// Additional search directories, equivalent to PlantUML's `plantuml.include.path`.
+    pub include_path: Vec<PathBuf>,
+}
+
+/// List the files a diagram includes, directly or transitively.
+///
+/// Directives are traced the same way PlantUML's preprocessor resolves them:
+/// relative to the including file first, then through `include_path`.
+/// Standard library (`<...>`) and URL includes are skipped. Includes that
+/// cannot be found are still reported (resolved against the including
+/// file's directory) so build systems can depend on them appearing later.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::IncludeOptions;
+/// let deps = plantuml::includes(
+///     "@startuml\n!include common.iuml\nAlice -> Bob\n@enduml",
+///     &IncludeOptions::default(),
+/// ).unwrap();
+/// ```
+pub fn includes(source: &str, opts: &IncludeOptions) -> Result<Vec<PathBuf>> {
+    let base_dir = opts.base_dir.clone().unwrap_or_default();
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+
+    trace(source, &base_dir, opts, &mut seen, &mut found)?;
+
+    Ok(found)
+}
+
+/// Check that everything `source` includes, directly or transitively, lies
+/// within one of the `allowed` directories.
+///
+/// Includes that can't be resolved statically (URLs, preprocessor
+/// expressions) are rejected outright, since they can't be checked; the
+/// standard library (`<...>`) ships inside the JAR and is always allowed.
+pub(crate) fn check_allowed(
+    source: &str,
+    opts: &IncludeOptions,
+    allowed: &[PathBuf],
+) -> Result<()> {
+    let allowed: Vec<PathBuf> = allowed
+        .iter()
+        .filter_map(|d| d.canonicalize().ok())
+        .collect();
+    let deps = includes(source, opts)?;
+
+    let mut sources = vec![source.to_string()];
+    for dep in &deps {
+        let canonical = dep
+            .canonicalize()
+            .ok()
+            .filter(|path| allowed.iter().any(|dir| path.starts_with(dir)));
+        let Some(canonical) = canonical else {
+            return Err(PlantUmlError::IncludeNotAllowed(dep.display().to_string()));
+        };
+
+        if canonical.is_file() {
+            sources.push(fs::read_to_string(&canonical).unwrap_or_default());
+        }
+    }
+
+    for source in &sources {
+        if let Some(line) = source.lines().find_map(untraceable_include) {
+            return Err(PlantUmlError::IncludeNotAllowed(line.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn trace(
+    source: &str,
+    dir: &Path,
+    opts: &IncludeOptions,
+    seen: &mut HashSet<PathBuf>,
+    found: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for target in source.lines().filter_map(include_target) {
+        let path = normalize(&resolve(target, dir, &opts.include_path));
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        found.push(path.clone());
+
+        // Archives pulled in via !import are not traced further
+        if !path.is_file() || target.ends_with(".zip") || target.ends_with(".jar") {
+            continue;
+        }
+
+        let nested = fs::read_to_string(&path).map_err(|source| PlantUmlError::InputRead {
+            path: path.clone(),
+            source,
+        })?;
+        let nested_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        trace(&nested, &nested_dir, opts, seen, found)?;
+    }
+
+    Ok(())
+}
+
+/// Extract the file referenced by an include directive line, if any.
+fn include_target(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let directive = INCLUDE_DIRECTIVES
+        .iter()
+        .find(|d| line.starts_with(**d) && line[d.len()..].starts_with(char::is_whitespace))?;
+    let target = line[directive.len()..].trim();
+
+    // Standard library, URLs and preprocessor expressions can't be traced statically
+    if target.starts_with('<')
+        || target.contains("://")
+        || target.contains('%')
+        || target.contains('$')
+    {
+        return None;
+    }
+
+    // Strip `!N` diagram index or `!PART` sub-part selectors
+    let target = target.split('!').next().unwrap_or(target).trim();
+    let target = target.trim_matches('"');
+
+    (!target.is_empty()).then_some(target)
+}
+
+/// Return an include directive line whose target can't be traced statically.
+fn untraceable_include(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if !(line.starts_with("!include") || line.starts_with("!import"))
+        || include_target(line).is_some()
+    {
+        return None;
+    }
+
+    let target = line
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, t)| t.trim());
+    (!target.starts_with('<')).then_some(line)
+}
+
+fn resolve(target: &str, dir: &Path, include_path: &[PathBuf]) -> PathBuf {
+    let local = dir.join(target);
+    if local.exists() {
+        return local;
+    }
+
+    include_path
+        .iter()
+        .map(|d| d.join(target))
+        .find(|p| p.exists())
+        .unwrap_or(local)
+}
+
+/// Lexically resolve `.` and `..` components so include cycles are detected.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push(component);
+                }
+            }
+            _ => out.push(component),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_target() {
+        assert_eq!(include_target("!include common.iuml"), Some("common.iuml"));
+        assert_eq!(
+            include_target("  !include_once a/b.puml!2"),
+            Some("a/b.puml")
+        );
+        assert_eq!(
+            include_target("!includesub parts.puml!BASIC"),
+            Some("parts.puml")
+        );
+        assert_eq!(include_target("!include <C4/C4_Container>"), None);
+        assert_eq!(include_target("!includeurl https://x/y.puml"), None);
+        assert_eq!(include_target("!include %dirpath()/x.puml"), None);
+        assert_eq!(include_target("Alice -> Bob"), None);
+    }
+
+    #[test]
+    fn test_check_allowed() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-jail-{}", std::process::id()));
+        let jail = dir.join("jail");
+        fs::create_dir_all(&jail).unwrap();
+        fs::write(jail.join("ok.iuml"), "skinparam monochrome true\n").unwrap();
+        fs::write(jail.join("escape.iuml"), "!include ../secret.txt\n").unwrap();
+        fs::write(dir.join("secret.txt"), "secret\n").unwrap();
+
+        let opts = IncludeOptions {
+            base_dir: Some(jail.clone()),
+            ..Default::default()
+        };
+        let allowed = [jail.clone()];
+        let check = |source: &str| check_allowed(source, &opts, &allowed);
+
+        assert!(check("!include ok.iuml\n!include <C4/C4_Container>").is_ok());
+        assert!(check("!include escape.iuml").is_err());
+        assert!(check("!include ../secret.txt").is_err());
+        assert!(check("!includeurl https://example.com/x.puml").is_err());
+        assert!(check("!include %getenv(\"HOME\")/x.puml").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_includes_transitive() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-includes-{}", std::process::id()));
+        let lib = dir.join("lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(dir.join("a.iuml"), "!include sub/b.iuml\n").unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("b.iuml"), "!include_once ../a.iuml\n").unwrap();
+        fs::write(lib.join("style.iuml"), "skinparam monochrome true\n").unwrap();
+
+        let opts = IncludeOptions {
+            base_dir: Some(dir.clone()),
+            include_path: vec![lib.clone()],
+        };
+        let deps = includes(
+            "@startuml\n!include a.iuml\n!include style.iuml\n!include missing.iuml\n@enduml",
+            &opts,
+        )
+        .unwrap();
+
+        assert_eq!(
+            deps,
+            vec![
+                dir.join("a.iuml"),
+                dir.join("sub").join("b.iuml"),
+                lib.join("style.iuml"),
+                dir.join("missing.iuml"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,46 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{PlantUmlError, Result};
+
+/// Strip a leading UTF-8 byte order mark and normalize CRLF line endings to LF.
+///
+/// PlantUML treats a BOM as part of the first line, so BOM-prefixed files
+/// fail with a confusing syntax error on `@startuml`.
+pub fn normalize_source(source: &str) -> Cow<'_, str> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+
+    if source.contains('\r') {
+        Cow::Owned(source.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(source)
+    }
+}
+
+/// Read a diagram source file and normalize it with [`normalize_source`].
+pub(crate) fn read_source(path: &Path) -> Result<String> {
+    let source = fs::read_to_string(path).map_err(|source| PlantUmlError::InputRead {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    Ok(normalize_source(&source).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(
+            normalize_source("\u{feff}@startuml\r\nA -> B\r\n@enduml\r\n"),
+            "@startuml\nA -> B\n@enduml\n"
+        );
+        assert!(matches!(
+            normalize_source("@startuml\nA -> B\n@enduml"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
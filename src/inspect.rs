@@ -0,0 +1,348 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::svg::diagram_title;
+
+/// Diagram type, as selected by the `@startX` tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiagramKind {
+    /// `@startuml`: sequence, class, activity, component, state, ... diagrams
+    Uml,
+    Mindmap,
+    Wbs,
+    Gantt,
+    Chronology,
+    Salt,
+    Json,
+    Yaml,
+    Ebnf,
+    Regex,
+    Ditaa,
+    /// `@startdot`: raw GraphViz input, rendered by an external `dot`
+    Dot,
+    Math,
+    Latex,
+    Creole,
+    Board,
+    Chen,
+    Files,
+    Wire,
+    Nwdiag,
+    /// A `@startX` tag not known to this crate; holds the tag suffix
+    Other(String),
+}
+
+impl DiagramKind {
+    /// Parse the suffix of a `@startX` tag, e.g. `"mindmap"`.
+    pub fn from_tag(tag: &str) -> Self {
+        match tag.to_ascii_lowercase().as_str() {
+            "uml" => Self::Uml,
+            "mindmap" => Self::Mindmap,
+            "wbs" => Self::Wbs,
+            "gantt" => Self::Gantt,
+            "chronology" => Self::Chronology,
+            "salt" => Self::Salt,
+            "json" => Self::Json,
+            "yaml" => Self::Yaml,
+            "ebnf" => Self::Ebnf,
+            "regex" => Self::Regex,
+            "ditaa" => Self::Ditaa,
+            "dot" => Self::Dot,
+            "math" => Self::Math,
+            "latex" => Self::Latex,
+            "creole" => Self::Creole,
+            "board" => Self::Board,
+            "chen" => Self::Chen,
+            "files" => Self::Files,
+            "wire" => Self::Wire,
+            "nwdiag" => Self::Nwdiag,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    /// The suffix of the `@startX` tag for this kind.
+    pub fn tag(&self) -> &str {
+        match self {
+            Self::Uml => "uml",
+            Self::Mindmap => "mindmap",
+            Self::Wbs => "wbs",
+            Self::Gantt => "gantt",
+            Self::Chronology => "chronology",
+            Self::Salt => "salt",
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Ebnf => "ebnf",
+            Self::Regex => "regex",
+            Self::Ditaa => "ditaa",
+            Self::Dot => "dot",
+            Self::Math => "math",
+            Self::Latex => "latex",
+            Self::Creole => "creole",
+            Self::Board => "board",
+            Self::Chen => "chen",
+            Self::Files => "files",
+            Self::Wire => "wire",
+            Self::Nwdiag => "nwdiag",
+            Self::Other(tag) => tag,
+        }
+    }
+
+    /// Whether this kind always needs a GraphViz `dot` executable.
+    ///
+    /// `@startuml` diagrams may also need it depending on their content
+    /// (e.g. class diagrams), unless the smetana layout engine is selected.
+    pub fn requires_graphviz(&self) -> bool {
+        matches!(self, Self::Dot)
+    }
+
+    /// Whether diagrams of this kind accept injected `!theme`, `!include`,
+    /// `title`, `caption`, `header` and `footer` directives. Data and raw
+    /// formats (JSON, YAML, GraphViz, ditaa, ...) treat them as content or
+    /// reject them.
+    pub(crate) fn accepts_directives(&self) -> bool {
+        matches!(
+            self,
+            Self::Uml | Self::Mindmap | Self::Wbs | Self::Gantt | Self::Chronology | Self::Nwdiag
+        )
+    }
+
+    /// Whether the bundled PlantUML release is known to render this kind.
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+}
+
+impl fmt::Display for DiagramKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@start{}", self.tag())
+    }
+}
+
+/// A single `@startX` ... `@endX` block found in a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagramInfo {
+    pub kind: DiagramKind,
+    /// Single-line `title` directive, if present
+    pub title: Option<String>,
+    /// 1-based line number of the `@startX` tag
+    pub start_line: usize,
+}
+
+/// Summary of the diagrams in a PlantUML source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inspection {
+    pub diagrams: Vec<DiagramInfo>,
+}
+
+impl Inspection {
+    /// Kind of the first diagram, if any.
+    pub fn kind(&self) -> Option<&DiagramKind> {
+        self.diagrams.first().map(|d| &d.kind)
+    }
+}
+
+/// Detect the diagrams in a source without rendering it.
+///
+/// # Example
+///
+/// ```
+/// use plantuml::DiagramKind;
+/// let info = plantuml::inspect("@startmindmap\n* root\n@endmindmap");
+/// assert_eq!(info.kind(), Some(&DiagramKind::Mindmap));
+/// ```
+pub fn inspect(source: &str) -> Inspection {
+    Inspection {
+        diagrams: blocks(source).into_iter().map(|(info, _)| info).collect(),
+    }
+}
+
+/// Split a source into its `@startX` ... `@endX` blocks, tags included.
+pub(crate) fn blocks(source: &str) -> Vec<(DiagramInfo, String)> {
+    let mut blocks = Vec::new();
+    // Kind, start line, full block text and body without the tags
+    let mut current: Option<(DiagramKind, usize, String, String)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+
+        match &mut current {
+            None => {
+                if let Some(tag) = start_tag(trimmed) {
+                    let text = format!("{}\n", line);
+                    current = Some((DiagramKind::from_tag(tag), i + 1, text, String::new()));
+                }
+            }
+            Some((_, _, text, body)) => {
+                text.push_str(line);
+                text.push('\n');
+
+                if trimmed.starts_with("@end") {
+                    let (kind, start_line, text, body) = current.take().unwrap();
+                    blocks.push((
+                        DiagramInfo {
+                            kind,
+                            title: diagram_title(&body),
+                            start_line,
+                        },
+                        text,
+                    ));
+                } else {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+            }
+        }
+    }
+
+    // An unterminated block is still reported; PlantUML will flag the error
+    if let Some((kind, start_line, text, body)) = current {
+        blocks.push((
+            DiagramInfo {
+                kind,
+                title: diagram_title(&body),
+                start_line,
+            },
+            text,
+        ));
+    }
+
+    blocks
+}
+
+/// Wrap a bare diagram fragment in `@startX`/`@endX` tags.
+///
+/// Sources that already contain a `@startX` line are returned unchanged.
+/// Otherwise the kind is guessed from the content: JSON documents, GraphViz
+/// graphs, Gantt task lines and `*`-bulleted mind maps are recognized, and
+/// anything else is treated as `@startuml`.
+///
+/// # Example
+///
+/// ```
+/// let wrapped = plantuml::wrap_fragment("Alice -> Bob: Hello");
+/// assert_eq!(wrapped, "@startuml\nAlice -> Bob: Hello\n@enduml\n");
+/// ```
+pub fn wrap_fragment(source: &str) -> Cow<'_, str> {
+    if source.lines().any(|line| start_tag(line.trim()).is_some()) {
+        return Cow::Borrowed(source);
+    }
+
+    let tag = guess_kind(source).tag().to_string();
+    let body = source.trim_end_matches(['\r', '\n']);
+
+    Cow::Owned(format!("@start{tag}\n{body}\n@end{tag}\n"))
+}
+
+/// Guess the diagram kind of a fragment without `@startX` tags.
+fn guess_kind(source: &str) -> DiagramKind {
+    let lines: Vec<&str> = source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('\''))
+        .collect();
+    let Some(first) = lines.first() else {
+        return DiagramKind::Uml;
+    };
+
+    if first.starts_with('{') || first.starts_with('[') && !first.contains(']') {
+        DiagramKind::Json
+    } else if first.starts_with("digraph") || first.starts_with("graph") {
+        DiagramKind::Dot
+    } else if lines.iter().any(|line| {
+        line.starts_with("project starts")
+            || line.starts_with('[') && (line.contains("] lasts") || line.contains("] requires"))
+    }) {
+        DiagramKind::Gantt
+    } else if lines.iter().all(|line| line.starts_with('*')) {
+        DiagramKind::Mindmap
+    } else {
+        DiagramKind::Uml
+    }
+}
+
+/// Extract the tag suffix of a `@startX` line, ignoring any `(id=...)` or file name.
+pub(crate) fn start_tag(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("@start")?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+
+    (end > 0).then(|| &rest[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_multiple_diagrams() {
+        let source = "' comment\n@startuml(id=first)\ntitle Login\nA -> B\n@enduml\n\n@startGANTT\n[Task] lasts 3 days\n@endgantt\n";
+        let info = inspect(source);
+
+        assert_eq!(
+            info.diagrams,
+            vec![
+                DiagramInfo {
+                    kind: DiagramKind::Uml,
+                    title: Some("Login".to_string()),
+                    start_line: 2,
+                },
+                DiagramInfo {
+                    kind: DiagramKind::Gantt,
+                    title: None,
+                    start_line: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_kind() {
+        let info = inspect("@startfoo\n@endfoo");
+        let kind = info.kind().unwrap();
+        assert_eq!(kind, &DiagramKind::Other("foo".to_string()));
+        assert!(!kind.is_supported());
+        assert_eq!(kind.to_string(), "@startfoo");
+    }
+
+    #[test]
+    fn test_wrap_fragment() {
+        assert_eq!(
+            wrap_fragment("* root\n** child\n"),
+            "@startmindmap\n* root\n** child\n@endmindmap\n"
+        );
+        assert_eq!(
+            wrap_fragment("{\"a\": 1}"),
+            "@startjson\n{\"a\": 1}\n@endjson\n"
+        );
+        assert_eq!(
+            wrap_fragment("[Design] lasts 5 days\n[Build] requires 10 days"),
+            "@startgantt\n[Design] lasts 5 days\n[Build] requires 10 days\n@endgantt\n"
+        );
+        assert_eq!(
+            wrap_fragment("digraph G { a -> b }"),
+            "@startdot\ndigraph G { a -> b }\n@enddot\n"
+        );
+
+        let complete = "@startuml\nA -> B\n@enduml";
+        assert!(matches!(wrap_fragment(complete), Cow::Borrowed(s) if s == complete));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = blocks("' comment\n@startuml\nA -> B\n@enduml\n@startwbs\n* root\n@endwbs");
+        let texts: Vec<&str> = blocks.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "@startuml\nA -> B\n@enduml\n",
+                "@startwbs\n* root\n@endwbs\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_diagrams() {
+        assert_eq!(inspect("Alice -> Bob").kind(), None);
+    }
+}
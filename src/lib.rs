@@ -10,14 +10,53 @@
 //! println!("{}", svg);
 //! ```
 
+mod archive;
+#[cfg(feature = "tokio")]
+mod async_render;
+mod batch;
 mod binary;
+pub mod build;
 mod error;
 mod executor;
+mod format;
+mod includes;
+mod input;
+mod inspect;
+mod metadata;
+mod options;
+mod output;
+mod renderer;
+mod stress;
+mod svg;
+mod theme;
 
-pub use binary::{get_bundle_paths, BundlePaths};
+pub use archive::render_archive;
+#[cfg(feature = "tokio")]
+pub use async_render::{
+    render_async, render_file_async, render_to_file_async, render_with_options_async,
+};
+pub use batch::{render_dir, BatchOptions, BatchReport, FileReport, FileStatus};
+pub use binary::{
+    bundled_jar_sha256, configure_bundle, extract_bundle_to, get_bundle_paths, BundleConfig,
+    BundlePaths, BUNDLE_DIR_ENV,
+};
 pub use error::{PlantUmlError, Result};
+pub use executor::RenderResult;
+pub use format::{EpsTextMode, OutputFormat};
+pub use includes::{includes, IncludeOptions};
+pub use input::normalize_source;
+pub use inspect::{inspect, wrap_fragment, DiagramInfo, DiagramKind, Inspection};
+pub use metadata::{read_provenance, Provenance};
+pub use options::{RenderOptions, ResourceLimits};
+pub use renderer::{
+    PoolOptions, PoolStats, PooledRenderer, Priority, RenderUsage, Renderer, RendererPool,
+};
+pub use stress::{load_test, LoadTestReport};
+pub use svg::{add_accessibility, Accessibility};
+pub use theme::list_themes;
 
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
 
 /// Render PlantUML syntax to an SVG string.
@@ -44,8 +83,297 @@ pub fn render(plantuml: &str) -> Result<String> {
     executor::execute_pipe(plantuml)
 }
 
+/// Render PlantUML syntax to SVG, returning the raw bytes PlantUML wrote.
+///
+/// Unlike [`render`], the output is not checked for valid UTF-8, so SVGs
+/// containing stray bytes (e.g. from non-UTF-8 sprites) are returned intact.
+///
+/// # Example
+///
+/// ```no_run
+/// let svg = plantuml::render_bytes("@startuml\nAlice -> Bob: Hello\n@enduml").unwrap();
+/// std::fs::write("diagram.svg", svg).unwrap();
+/// ```
+pub fn render_bytes(plantuml: &str) -> Result<Vec<u8>> {
+    executor::execute_pipe_bytes(plantuml, OutputFormat::Svg)
+}
+
+/// Render PlantUML syntax to the given output format.
+///
+/// Returns the raw bytes PlantUML produced; text formats such as
+/// [`OutputFormat::Svg`] or [`OutputFormat::Txt`] are UTF-8.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::OutputFormat;
+/// let txt = plantuml::render_with_format(
+///     "@startuml\nAlice -> Bob: Hello\n@enduml",
+///     OutputFormat::Txt,
+/// ).unwrap();
+/// ```
+pub fn render_with_format(plantuml: &str, format: OutputFormat) -> Result<Vec<u8>> {
+    executor::execute_pipe_bytes(plantuml, format)
+}
+
+/// Render PlantUML syntax to the given format, streaming the output into `writer`.
+///
+/// Output is copied to `writer` as PlantUML produces it instead of being
+/// buffered, which keeps memory flat for multi-megabyte PNGs written to a
+/// file, socket or HTTP response. If rendering fails, `writer` may already
+/// have received partial output.
+///
+/// # Returns
+///
+/// The number of bytes written.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use plantuml::OutputFormat;
+///
+/// let mut file = File::create("diagram.png").unwrap();
+/// plantuml::render_to_writer(
+///     "@startuml\nAlice -> Bob: Hello\n@enduml",
+///     OutputFormat::Png,
+///     &mut file,
+/// ).unwrap();
+/// ```
+pub fn render_to_writer(
+    plantuml: &str,
+    format: OutputFormat,
+    writer: &mut impl Write,
+) -> Result<u64> {
+    executor::execute_to_writer(
+        plantuml.as_bytes(),
+        &RenderOptions::new().format(format),
+        writer,
+    )
+}
+
+/// Render PlantUML syntax to PNG image bytes.
+///
+/// # Example
+///
+/// ```no_run
+/// let png = plantuml::render_png("@startuml\nAlice -> Bob: Hello\n@enduml").unwrap();
+/// std::fs::write("diagram.png", png).unwrap();
+/// ```
+pub fn render_png(plantuml: &str) -> Result<Vec<u8>> {
+    render_with_format(plantuml, OutputFormat::Png)
+}
+
+/// Render PlantUML syntax to a PNG file.
+///
+/// The output is replaced atomically, like [`render_file`].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// plantuml::render_png_to_file(
+///     "@startuml\nAlice -> Bob: Hello\n@enduml",
+///     Path::new("diagram.png")
+/// ).unwrap();
+/// ```
+pub fn render_png_to_file(plantuml: &str, output: &Path) -> Result<()> {
+    let png = render_png(plantuml)?;
+
+    output::write_atomic(output, &png)
+}
+
+/// Render PlantUML syntax to a PDF document.
+///
+/// # Example
+///
+/// ```no_run
+/// let pdf = plantuml::render_pdf("@startuml\nAlice -> Bob: Hello\n@enduml").unwrap();
+/// std::fs::write("diagram.pdf", pdf).unwrap();
+/// ```
+pub fn render_pdf(plantuml: &str) -> Result<Vec<u8>> {
+    render_with_format(plantuml, OutputFormat::Pdf)
+}
+
+/// Render PlantUML syntax to an Encapsulated PostScript document.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::EpsTextMode;
+/// let eps = plantuml::render_eps(
+///     "@startuml\nAlice -> Bob: Hello\n@enduml",
+///     EpsTextMode::Text,
+/// ).unwrap();
+/// ```
+pub fn render_eps(plantuml: &str, text: EpsTextMode) -> Result<Vec<u8>> {
+    render_with_format(plantuml, text.into())
+}
+
+/// Render PlantUML syntax with the given options.
+///
+/// Returns the raw bytes in the format selected with
+/// [`RenderOptions::format`] (SVG by default).
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::{OutputFormat, RenderOptions};
+/// let png = plantuml::render_with_options(
+///     "Alice -> Bob: Hello",
+///     &RenderOptions::new().auto_wrap(true).format(OutputFormat::Png),
+/// ).unwrap();
+/// ```
+pub fn render_with_options(plantuml: &str, options: &RenderOptions) -> Result<Vec<u8>> {
+    let source = options.prepare_source(plantuml);
+    let output = executor::execute(source.as_bytes(), options)?;
+
+    postprocess(output, Some(plantuml), options)
+}
+
+/// Render PlantUML syntax with the given options and keep PlantUML's stderr.
+///
+/// Like [`render_with_options`], but a successful render also returns the
+/// raw stderr in [`RenderResult::raw_stderr`], for tracking down diagrams
+/// that render without an error but don't look as expected. Failed renders
+/// carry their stderr in the error as usual.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::RenderOptions;
+/// let result = plantuml::render_with_stderr(
+///     "Alice -> Bob: Hello",
+///     &RenderOptions::new(),
+/// ).unwrap();
+/// eprintln!("{}", result.raw_stderr);
+/// ```
+pub fn render_with_stderr(plantuml: &str, options: &RenderOptions) -> Result<RenderResult> {
+    let source = options.prepare_source(plantuml);
+    let result = executor::execute_with_stderr(source.as_bytes(), options)?;
+
+    Ok(RenderResult {
+        output: postprocess(result.output, Some(plantuml), options)?,
+        raw_stderr: result.raw_stderr,
+    })
+}
+
+/// Render a PlantUML source given as raw bytes in the charset set with
+/// [`RenderOptions::charset`].
+///
+/// Use this for sources in encodings other than UTF-8, which would otherwise
+/// have to be converted lossily to a `&str` first. The bytes are passed to
+/// PlantUML unchanged, so source-level options (auto-wrapping, theme,
+/// includes and injected title/caption/header/footer) and provenance are
+/// not applied; process and output options, including scale, are.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::RenderOptions;
+/// let source = std::fs::read("legacy.puml").unwrap();
+/// let svg = plantuml::render_encoded(
+///     &source,
+///     &RenderOptions::new().charset("windows-1252"),
+/// ).unwrap();
+/// ```
+pub fn render_encoded(plantuml: &[u8], options: &RenderOptions) -> Result<Vec<u8>> {
+    let output = executor::execute(plantuml, options)?;
+
+    postprocess(output, None, options)
+}
+
+/// Apply SVG post-processing options, then the user's hooks, to rendered output.
+///
+/// Provenance is only embedded when the original `source` text is known.
+fn postprocess(output: Vec<u8>, source: Option<&str>, options: &RenderOptions) -> Result<Vec<u8>> {
+    let mut output = postprocess_svg(output, source, options)?;
+    for hook in &options.post_processors {
+        output = hook.apply(output)?;
+    }
+
+    Ok(output)
+}
+
+fn postprocess_svg(
+    output: Vec<u8>,
+    source: Option<&str>,
+    options: &RenderOptions,
+) -> Result<Vec<u8>> {
+    let provenance = options.provenance && source.is_some();
+    if options.format != OutputFormat::Svg || (options.fit.is_none() && !provenance) {
+        return Ok(output);
+    }
+
+    let mut svg = String::from_utf8(output).map_err(PlantUmlError::InvalidUtf8)?;
+    if let Some((width, height)) = options.fit {
+        svg = svg::fit(&svg, width, height);
+    }
+    if let Some(source) = source.filter(|_| provenance) {
+        svg = Provenance::new(source, options)?.embed(&svg);
+    }
+
+    Ok(svg.into_bytes())
+}
+
+/// Render a PlantUML source read from `reader`, without materializing it as a `String`.
+///
+/// The source is streamed to PlantUML as it is read, which suits large
+/// generated sources or archive entries. The reader runs on a separate
+/// thread so [`RenderOptions::timeout`] covers it, hence the `Send +
+/// 'static` bound; a failing reader is reported as
+/// [`PlantUmlError::InputRead`]. As with [`render_encoded`],
+/// source-level options and provenance are not applied, and the bytes are
+/// decoded with [`RenderOptions::charset`].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::File;
+/// use plantuml::RenderOptions;
+///
+/// let source = File::open("generated.puml").unwrap();
+/// let svg = plantuml::render_from_reader(source, &RenderOptions::new()).unwrap();
+/// ```
+pub fn render_from_reader(
+    reader: impl Read + Send + 'static,
+    options: &RenderOptions,
+) -> Result<Vec<u8>> {
+    let output = executor::execute_from_reader(Box::new(reader), options)?;
+
+    postprocess(output, None, options)
+}
+
+/// Render PlantUML syntax to an accessible SVG string.
+///
+/// The SVG root gets `role="img"`, an `aria-label`, and `<title>`/`<desc>`
+/// elements taken from the diagram's `title` directive and `alt_text`.
+///
+/// # Example
+///
+/// ```no_run
+/// let svg = plantuml::render_accessible(
+///     "@startuml\ntitle Login\nAlice -> Bob: Hello\n@enduml",
+///     Some("Alice sends a greeting to Bob"),
+/// ).unwrap();
+/// ```
+pub fn render_accessible(plantuml: &str, alt_text: Option<&str>) -> Result<String> {
+    let svg = executor::execute_pipe(plantuml)?;
+    let a11y = Accessibility::from_source(plantuml, alt_text);
+
+    Ok(add_accessibility(&svg, &a11y))
+}
+
 /// Render a PlantUML file to an SVG file.
 ///
+/// The input is normalized with [`normalize_source`] (BOM stripped, CRLF
+/// converted to LF) before rendering; use [`render_to_file`] with the raw
+/// contents to bypass this. Relative `!include` targets resolve against
+/// the directory of `input`, wherever the process runs from.
+///
+/// The output is replaced atomically: if rendering fails or the process
+/// crashes, any previous file at `output` is left intact.
+///
 /// # Arguments
 ///
 /// * `input` - Path to the input PlantUML file
@@ -61,23 +389,58 @@ pub fn render(plantuml: &str) -> Result<String> {
 /// ).unwrap();
 /// ```
 pub fn render_file(input: &Path, output: &Path) -> Result<()> {
-    let plantuml = fs::read_to_string(input).map_err(|source| PlantUmlError::InputRead {
-        path: input.to_path_buf(),
-        source,
-    })?;
+    let plantuml = input::read_source(input)?;
 
-    let svg = executor::execute_pipe(&plantuml)?;
+    render_source_file(input, &plantuml, output)
+}
 
-    fs::write(output, &svg).map_err(|source| PlantUmlError::OutputWrite {
-        path: output.to_path_buf(),
-        source,
-    })?;
+/// Render the contents of `input` to `output` as if PlantUML had read the file.
+fn render_source_file(input: &Path, plantuml: &str, output: &Path) -> Result<()> {
+    // Name the real file in error messages and resolve includes next to it
+    let options = RenderOptions::new().source_file(input);
+    let svg = executor::execute(plantuml.as_bytes(), &options)?;
 
-    Ok(())
+    output::write_atomic(output, &svg)
+}
+
+/// Render a PlantUML file to an SVG file, skipping it if the output is current.
+///
+/// PlantUML embeds the diagram source in the SVG it produces. If `output`
+/// already exists and its embedded source matches the contents of `input`,
+/// the render is skipped. This is the pipe-mode equivalent of PlantUML's
+/// `-checkmetadata` flag and makes re-runs over large trees cheap.
+///
+/// # Returns
+///
+/// `true` if the diagram was rendered, `false` if it was already up to date.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// let rendered = plantuml::render_file_if_changed(
+///     Path::new("diagram.puml"),
+///     Path::new("diagram.svg")
+/// ).unwrap();
+/// ```
+pub fn render_file_if_changed(input: &Path, output: &Path) -> Result<bool> {
+    let plantuml = input::read_source(input)?;
+
+    if let Ok(existing) = fs::read_to_string(output) {
+        if metadata::is_up_to_date(&existing, &plantuml) {
+            return Ok(false);
+        }
+    }
+
+    render_source_file(input, &plantuml, output)?;
+
+    Ok(true)
 }
 
 /// Render PlantUML syntax to an SVG file.
 ///
+/// The output is replaced atomically, like [`render_file`].
+///
 /// # Arguments
 ///
 /// * `plantuml` - PlantUML source code as a string
@@ -93,14 +456,9 @@ pub fn render_file(input: &Path, output: &Path) -> Result<()> {
 /// ).unwrap();
 /// ```
 pub fn render_to_file(plantuml: &str, output: &Path) -> Result<()> {
-    let svg = executor::execute_pipe(plantuml)?;
+    let svg = render_bytes(plantuml)?;
 
-    fs::write(output, &svg).map_err(|source| PlantUmlError::OutputWrite {
-        path: output.to_path_buf(),
-        source,
-    })?;
-
-    Ok(())
+    output::write_atomic(output, &svg)
 }
 
 #[cfg(test)]
@@ -117,6 +475,133 @@ mod tests {
         assert!(svg.contains("<svg"), "Output should be SVG");
     }
 
+    #[test]
+    fn test_render_bytes() {
+        let svg = render_bytes("@startuml\nAlice -> Bob: Hello\n@enduml").unwrap();
+        let text = render("@startuml\nAlice -> Bob: Hello\n@enduml").unwrap();
+        assert_eq!(svg, text.into_bytes());
+    }
+
+    #[test]
+    fn test_render_file_if_changed() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("diagram.puml");
+        let output = dir.join("diagram.svg");
+        fs::write(&input, "@startuml\nAlice -> Bob: Hello\n@enduml\n").unwrap();
+
+        let first = render_file_if_changed(&input, &output);
+        assert!(
+            matches!(first, Ok(true)),
+            "First run should render: {:?}",
+            first
+        );
+
+        let second = render_file_if_changed(&input, &output);
+        assert!(
+            matches!(second, Ok(false)),
+            "Unchanged input should be skipped"
+        );
+
+        fs::write(&input, "@startuml\nAlice -> Bob: Bye\n@enduml\n").unwrap();
+        let third = render_file_if_changed(&input, &output);
+        assert!(matches!(third, Ok(true)), "Changed input should render");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_file_resolves_relative_includes() {
+        // The test runs from the crate root, not the diagram's directory
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-inc-{}", std::process::id()));
+        let docs = dir.join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        fs::write(docs.join("common.iuml"), "Shared -> Style\n").unwrap();
+        let input = docs.join("a.puml");
+        fs::write(&input, "@startuml\n!include common.iuml\nA -> B\n@enduml\n").unwrap();
+        let output = dir.join("a.svg");
+
+        render_file(&input, &output).unwrap();
+        let svg = fs::read_to_string(&output).unwrap();
+        assert!(svg.contains("Shared"), "Included file should be rendered");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_render_with_auto_wrap() {
+        let options = RenderOptions::new().auto_wrap(true);
+        let result = render_with_options("Alice -> Bob: Hello", &options);
+        assert!(result.is_ok(), "Fragment should render: {:?}", result);
+    }
+
+    #[test]
+    fn test_render_with_options_format() {
+        let options = RenderOptions::new().format(OutputFormat::Png);
+        let result = render_with_options("@startuml\nAlice -> Bob\n@enduml", &options);
+        assert!(
+            result.unwrap().starts_with(b"\x89PNG"),
+            "Output should be a PNG"
+        );
+    }
+
+    #[test]
+    fn test_render_with_post_process() {
+        let options = RenderOptions::new()
+            .post_process(|svg| Ok([b"<!-- first -->".as_slice(), &svg].concat()))
+            .post_process(|svg| Ok([b"<!-- second -->".as_slice(), &svg].concat()));
+        let svg = render_with_options("@startuml\nA -> B\n@enduml", &options).unwrap();
+        assert!(svg.starts_with(b"<!-- second --><!-- first --><"));
+
+        let options = RenderOptions::new()
+            .post_process(|_| Err(PlantUmlError::SyntaxError("rejected".into())));
+        assert!(render_with_options("@startuml\nA -> B\n@enduml", &options).is_err());
+    }
+
+    #[test]
+    fn test_render_untrusted_preset() {
+        let options = RenderOptions::for_untrusted();
+        let svg = render_with_options("@startuml\nA -> B\n@enduml", &options).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("<svg"));
+
+        let result = render_with_options("@startuml\n!include /etc/passwd\n@enduml", &options);
+        assert!(matches!(result, Err(PlantUmlError::IncludeNotAllowed(_))));
+    }
+
+    #[test]
+    fn test_render_with_stderr_keeps_raw_stderr() {
+        let options = RenderOptions::new()
+            .quiet(true)
+            .env("_JAVA_OPTIONS", "-Xmx256m");
+        let result = render_with_stderr("@startuml\nAlice -> Bob\n@enduml", &options).unwrap();
+        assert!(String::from_utf8(result.output).unwrap().contains("<svg"));
+        assert!(
+            result.raw_stderr.contains("Picked up _JAVA_OPTIONS"),
+            "quiet should not filter the raw stderr: {:?}",
+            result.raw_stderr
+        );
+    }
+
+    #[test]
+    fn test_render_with_format() {
+        let input = "@startuml\nAlice -> Bob: Hello\n@enduml";
+        let result = render_with_format(input, OutputFormat::Txt);
+        assert!(result.is_ok(), "Failed to render text: {:?}", result);
+
+        let txt = String::from_utf8(result.unwrap()).unwrap();
+        assert!(txt.contains("Alice"), "Text output should contain Alice");
+    }
+
+    #[test]
+    fn test_render_pdf() {
+        let result = render_pdf("@startuml\nAlice -> Bob: Hello\n@enduml");
+        assert!(result.is_ok(), "Failed to render PDF: {:?}", result);
+        assert!(
+            result.unwrap().starts_with(b"%PDF"),
+            "Output should be a PDF"
+        );
+    }
+
     #[test]
     fn test_render_sequence_diagram() {
         let input = r#"@startuml
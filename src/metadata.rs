@@ -0,0 +1,273 @@
+use flate2::read::DeflateDecoder;
+use std::fmt::Write;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::binary::{bundled_jar_sha256, sha256_hex};
+use crate::error::Result;
+use crate::options::RenderOptions;
+use crate::svg::find_root_tag;
+
+/// Processing instruction PlantUML uses to embed the diagram source in SVG output.
+const SOURCE_PI_START: &str = "<?plantuml-src ";
+const SOURCE_PI_END: &str = "?>";
+
+/// Processing instruction this crate uses to embed [`Provenance`] in SVG output.
+const PROVENANCE_PI_START: &str = "<?plantuml-rs-provenance ";
+
+/// Version of [`options_encoding`]; bump it whenever the encoding changes.
+const OPTIONS_ENCODING_VERSION: u32 = 1;
+
+/// Record of how an SVG was rendered, embedded with
+/// [`RenderOptions::provenance`](crate::RenderOptions::provenance).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// SHA-256 of the diagram source, before options were applied
+    pub source_sha256: String,
+    /// SHA-256 of the render options that affect the output
+    pub options_sha256: String,
+    /// Version of plantuml-rs that rendered the diagram
+    pub crate_version: String,
+    /// SHA-256 of the PlantUML JAR that rendered the diagram
+    pub jar_sha256: String,
+    /// Render time, in seconds since the Unix epoch
+    pub rendered_at: u64,
+}
+
+impl Provenance {
+    /// Provenance for rendering `source` with `options` now, using the bundled JAR.
+    pub(crate) fn new(source: &str, options: &RenderOptions) -> Result<Self> {
+        Ok(Self {
+            source_sha256: hash(source),
+            options_sha256: hash(&options_encoding(options)),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            jar_sha256: bundled_jar_sha256()?.to_string(),
+            rendered_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        })
+    }
+
+    /// Whether rendering `source` with `options` and the bundled JAR would
+    /// reproduce this output, i.e. whether the diagram is up to date.
+    pub fn is_current(&self, source: &str, options: &RenderOptions) -> bool {
+        self.source_sha256 == hash(source)
+            && self.options_sha256 == hash(&options_encoding(options))
+            && bundled_jar_sha256().is_ok_and(|jar| jar == self.jar_sha256)
+    }
+
+    /// Insert this provenance into `svg`, right after the root start tag.
+    pub(crate) fn embed(&self, svg: &str) -> String {
+        let Some((_, tag_end)) = find_root_tag(svg) else {
+            return svg.to_string();
+        };
+
+        format!(
+            "{}{}source-sha256=\"{}\" options-sha256=\"{}\" crate-version=\"{}\" jar-sha256=\"{}\" rendered-at=\"{}\"{}{}",
+            &svg[..tag_end],
+            PROVENANCE_PI_START,
+            self.source_sha256,
+            self.options_sha256,
+            self.crate_version,
+            self.jar_sha256,
+            self.rendered_at,
+            SOURCE_PI_END,
+            &svg[tag_end..]
+        )
+    }
+}
+
+/// Read the [`Provenance`] embedded in a rendered SVG, if any.
+///
+/// # Example
+///
+/// ```no_run
+/// use plantuml::RenderOptions;
+///
+/// let source = "@startuml\nAlice -> Bob\n@enduml";
+/// let options = RenderOptions::new();
+/// let svg = std::fs::read_to_string("diagram.svg").unwrap();
+///
+/// let stale = plantuml::read_provenance(&svg)
+///     .map_or(true, |p| !p.is_current(source, &options));
+/// ```
+pub fn read_provenance(svg: &str) -> Option<Provenance> {
+    let start = svg.find(PROVENANCE_PI_START)? + PROVENANCE_PI_START.len();
+    let len = svg[start..].find(SOURCE_PI_END)?;
+    let body = &svg[start..start + len];
+
+    let field = |name: &str| {
+        let key = format!("{}=\"", name);
+        let value_start = body.find(&key)? + key.len();
+        let value_len = body[value_start..].find('"')?;
+        Some(body[value_start..value_start + value_len].to_string())
+    };
+
+    Some(Provenance {
+        source_sha256: field("source-sha256")?,
+        options_sha256: field("options-sha256")?,
+        crate_version: field("crate-version")?,
+        jar_sha256: field("jar-sha256")?,
+        rendered_at: field("rendered-at")?.parse().ok()?,
+    })
+}
+
+/// Fixed encoding of the options that affect the output, for
+/// [`Provenance::options_sha256`].
+///
+/// Options such as the timeout or resource limits are left out, so tuning
+/// them doesn't make every diagram look stale. Values are length-prefixed
+/// so that no two option sets share an encoding.
+fn options_encoding(options: &RenderOptions) -> String {
+    let mut encoding = format!("plantuml-rs-options v{}\n", OPTIONS_ENCODING_VERSION);
+    let mut field = |name: &str, values: &[&str]| {
+        encoding.push_str(name);
+        for value in values {
+            let _ = write!(encoding, " {}:{}", value.len(), value);
+        }
+        encoding.push('\n');
+    };
+
+    field("format", &[&options.format.to_string()]);
+    field("scale", options.scale.as_deref().as_slice());
+    field("theme", options.theme.as_deref().as_slice());
+    field("smetana", &[&options.smetana.to_string()]);
+    for (name, value) in &options.defines {
+        field("define", &[name, value]);
+    }
+    for dir in &options.include_dirs {
+        field("include-dir", &[&dir.to_string_lossy()]);
+    }
+    match options.fit {
+        Some((width, height)) => field("fit", &[&width.to_string(), &height.to_string()]),
+        None => field("fit", &[]),
+    }
+    field("provenance", &[&options.provenance.to_string()]);
+
+    encoding
+}
+
+fn hash(text: &str) -> String {
+    sha256_hex(&mut text.as_bytes()).expect("hashing in-memory data cannot fail")
+}
+
+/// Extract the diagram source PlantUML embedded in a rendered SVG.
+///
+/// Returns `None` if the SVG carries no source metadata or it cannot be decoded.
+pub fn embedded_source(svg: &str) -> Option<String> {
+    let start = svg.find(SOURCE_PI_START)? + SOURCE_PI_START.len();
+    let len = svg[start..].find(SOURCE_PI_END)?;
+    decode(svg[start..start + len].trim())
+}
+
+/// Check whether `svg` was rendered from `source`, according to its embedded metadata.
+pub fn is_up_to_date(svg: &str, source: &str) -> bool {
+    embedded_source(svg).is_some_and(|embedded| normalize(&embedded) == normalize(source))
+}
+
+fn normalize(source: &str) -> String {
+    source.replace("\r\n", "\n").trim().to_string()
+}
+
+/// Decode PlantUML's text encoding: raw DEFLATE followed by a custom base64 alphabet.
+fn decode(encoded: &str) -> Option<String> {
+    let mut compressed = Vec::with_capacity(encoded.len() * 3 / 4);
+
+    for chunk in encoded.as_bytes().chunks(4) {
+        let mut group = [0u8; 4];
+        for (slot, &c) in group.iter_mut().zip(chunk) {
+            *slot = decode_6bit(c)?;
+        }
+
+        let bytes = [
+            (group[0] << 2) | (group[1] >> 4),
+            (group[1] << 4) | (group[2] >> 2),
+            (group[2] << 6) | group[3],
+        ];
+        compressed.extend_from_slice(&bytes[..chunk.len().saturating_sub(1)]);
+    }
+
+    let mut source = String::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .read_to_string(&mut source)
+        .ok()?;
+    Some(source)
+}
+
+fn decode_6bit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'A'..=b'Z' => Some(c - b'A' + 10),
+        b'a'..=b'z' => Some(c - b'a' + 36),
+        b'-' => Some(62),
+        b'_' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "@startuml\nA -> B\n@enduml";
+    const ENCODED: &str = "SoWkIImgAStDuN9KqBLJSE9oICrB0N81";
+
+    #[test]
+    fn test_decode_known_encoding() {
+        assert_eq!(decode(ENCODED).as_deref(), Some(SOURCE));
+    }
+
+    #[test]
+    fn test_provenance_round_trip() {
+        let options = RenderOptions::new();
+        let provenance = Provenance::new(SOURCE, &options).unwrap();
+
+        let svg = provenance.embed("<svg width=\"10px\"><g/></svg>");
+        assert!(svg.starts_with("<svg width=\"10px\"><?plantuml-rs-provenance "));
+
+        let read = read_provenance(&svg).unwrap();
+        assert_eq!(read, provenance);
+        assert!(read.is_current(SOURCE, &options));
+        assert!(!read.is_current("@startuml\nA -> C\n@enduml", &options));
+        assert!(!read.is_current(SOURCE, &RenderOptions::new().theme("plain")));
+        // Options that don't affect the output don't make it stale
+        let tuned = RenderOptions::new()
+            .timeout(std::time::Duration::from_secs(5))
+            .quiet(true);
+        assert!(read.is_current(SOURCE, &tuned));
+    }
+
+    #[test]
+    fn test_options_encoding() {
+        let options = RenderOptions::new()
+            .theme("plain")
+            .define("A", "1 2")
+            .fit(640, 480);
+
+        // Pinned: changing this invalidates every embedded provenance, so
+        // bump OPTIONS_ENCODING_VERSION along with it
+        assert_eq!(
+            options_encoding(&options),
+            "plantuml-rs-options v1\n\
+             format 3:svg\n\
+             scale\n\
+             theme 5:plain\n\
+             smetana 5:false\n\
+             define 1:A 3:1 2\n\
+             fit 3:640 3:480\n\
+             provenance 5:false\n"
+        );
+        assert_ne!(
+            options_encoding(&RenderOptions::new().define("A", "1").define("B", "2")),
+            options_encoding(&RenderOptions::new().define("A", "1 B=2"))
+        );
+    }
+
+    #[test]
+    fn test_is_up_to_date() {
+        let svg = format!("<svg><?plantuml-src {}?></svg>", ENCODED);
+        assert!(is_up_to_date(&svg, "@startuml\r\nA -> B\r\n@enduml\r\n"));
+        assert!(!is_up_to_date(&svg, "@startuml\nA -> C\n@enduml"));
+        assert!(!is_up_to_date("<svg></svg>", SOURCE));
+    }
+}
@@ -0,0 +1,971 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{PlantUmlError, Result};
+use crate::executor::{MAX_STDERR_BYTES, MAX_STDOUT_BYTES};
+use crate::format::OutputFormat;
+use crate::includes::IncludeOptions;
+use crate::inspect::{start_tag, wrap_fragment, DiagramKind};
+
+/// Limits applied to the PlantUML process, for rendering untrusted input.
+///
+/// CPU time and file size are enforced with `setrlimit` and are only
+/// available on Unix; a process exceeding them is killed by a signal
+/// ([`PlantUmlError::ProcessSignaled`]). Memory caps the Java heap (`-Xmx`)
+/// and works everywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum CPU time, rounded up to whole seconds.
+    pub cpu_time: Option<Duration>,
+    /// Maximum Java heap size in bytes.
+    pub memory: Option<u64>,
+    /// Maximum size in bytes of any file the process writes.
+    pub file_size: Option<u64>,
+}
+
+/// A user transformation of rendered output, see [`RenderOptions::post_process`].
+#[derive(Clone)]
+pub(crate) struct PostProcessor(Arc<dyn Fn(Vec<u8>) -> Result<Vec<u8>> + Send + Sync>);
+
+impl PostProcessor {
+    pub(crate) fn apply(&self, output: Vec<u8>) -> Result<Vec<u8>> {
+        (self.0)(output)
+    }
+}
+
+impl fmt::Debug for PostProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PostProcessor(..)")
+    }
+}
+
+/// Settings for a single render, used with [`render_with_options`](crate::render_with_options).
+///
+/// Built with chained setters starting from [`RenderOptions::new`]:
+///
+/// ```
+/// use std::time::Duration;
+/// use plantuml::{OutputFormat, RenderOptions};
+///
+/// let options = RenderOptions::new()
+///     .format(OutputFormat::Png)
+///     .theme("cerulean")
+///     .define("ENV", "prod")
+///     .timeout(Duration::from_secs(30))
+///     .jvm_arg("-Xmx512m");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub(crate) format: OutputFormat,
+    pub(crate) charset: Option<String>,
+    pub(crate) theme: Option<String>,
+    pub(crate) includes: Vec<PathBuf>,
+    pub(crate) defines: Vec<(String, String)>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) extra_jvm_args: Vec<String>,
+    pub(crate) auto_wrap: bool,
+    pub(crate) language: Option<String>,
+    pub(crate) country: Option<String>,
+    pub(crate) timezone: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) caption: Option<String>,
+    pub(crate) header: Option<String>,
+    pub(crate) footer: Option<String>,
+    pub(crate) fit: Option<(u32, u32)>,
+    pub(crate) scale: Option<String>,
+    pub(crate) dpi: Option<u32>,
+    pub(crate) config: Option<PathBuf>,
+    pub(crate) include_dirs: Vec<PathBuf>,
+    pub(crate) provenance: bool,
+    pub(crate) filename: Option<String>,
+    pub(crate) include_allowlist: Option<Vec<PathBuf>>,
+    pub(crate) quiet: bool,
+    pub(crate) smetana: bool,
+    pub(crate) no_graphviz_fallback: bool,
+    pub(crate) leave_running: bool,
+    pub(crate) retries: u32,
+    pub(crate) retry_backoff: Duration,
+    pub(crate) limits: ResourceLimits,
+    pub(crate) nice: Option<i32>,
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) env_clear: bool,
+    pub(crate) max_stdout_bytes: Option<usize>,
+    pub(crate) max_stderr_bytes: Option<usize>,
+    pub(crate) max_source_bytes: Option<usize>,
+    pub(crate) max_source_lines: Option<usize>,
+    pub(crate) post_processors: Vec<PostProcessor>,
+}
+
+impl RenderOptions {
+    /// Options matching the behavior of [`render`](crate::render).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preset for documentation builds: output that doesn't depend on the
+    /// machine rendering it.
+    ///
+    /// Pins the JVM locale to `en_US` and the time zone to UTC, so dates in
+    /// Gantt and timing diagrams come out the same everywhere, and drops
+    /// benign JVM warnings from errors ([`quiet`](Self::quiet)). Further
+    /// setters can be chained to adjust it.
+    pub fn for_docs() -> Self {
+        Self::new()
+            .language("en")
+            .country("US")
+            .timezone("UTC")
+            .quiet(true)
+    }
+
+    /// Preset for CI: [`for_docs`](Self::for_docs), plus a 60 second
+    /// timeout so a stuck render fails the build instead of hanging it, and
+    /// two retries of transient start-up failures, which are common on
+    /// freshly provisioned runners.
+    pub fn for_ci() -> Self {
+        Self::for_docs()
+            .timeout(Duration::from_secs(60))
+            .retry(2, Duration::from_millis(500))
+    }
+
+    /// Preset for rendering input from untrusted users.
+    ///
+    /// * a 10 second timeout and, on Unix, 10 seconds of CPU time
+    /// * a 512 MiB Java heap
+    /// * no file includes at all ([`include_allowlist`](Self::include_allowlist)
+    ///   with no directories); the bundled standard library stays available
+    /// * sources of at most 1 MiB and 10,000 lines
+    /// * at most 32 MiB of output and 64 KiB of stderr
+    ///
+    /// Loosen individual limits by chaining the corresponding setters.
+    pub fn for_untrusted() -> Self {
+        let limits = ResourceLimits {
+            #[cfg(unix)]
+            cpu_time: Some(Duration::from_secs(10)),
+            memory: Some(512 * 1024 * 1024),
+            ..Default::default()
+        };
+
+        Self::new()
+            .timeout(Duration::from_secs(10))
+            .limits(limits)
+            .include_allowlist(Vec::<PathBuf>::new())
+            .max_source_bytes(1024 * 1024)
+            .max_source_lines(10_000)
+            .max_stdout_bytes(32 * 1024 * 1024)
+            .max_stderr_bytes(64 * 1024)
+            .quiet(true)
+    }
+
+    /// Options from `PLANTUML_RS_*` environment variables, so renders can be
+    /// tuned in containers without code changes.
+    ///
+    /// | Variable | Meaning |
+    /// |---|---|
+    /// | `PLANTUML_RS_SECURITY_PROFILE` | `docs`, `ci` or `untrusted` to start from [`for_docs`](Self::for_docs), [`for_ci`](Self::for_ci) or [`for_untrusted`](Self::for_untrusted); `default` starts from [`new`](Self::new) |
+    /// | `PLANTUML_RS_TIMEOUT` | [`timeout`](Self::timeout) in seconds, e.g. `30` or `2.5` |
+    /// | `PLANTUML_RS_FORMAT` | [`format`](Self::format), e.g. `png` or `eps:text` |
+    /// | `PLANTUML_RS_JVM_ARGS` | extra [JVM arguments](Self::jvm_arg), separated by whitespace |
+    /// | `PLANTUML_RS_SMETANA` | `true` or `false`, see [`smetana`](Self::smetana) |
+    ///
+    /// Unset or empty variables keep the profile's setting. A value that
+    /// can't be parsed fails with [`PlantUmlError::InvalidEnvVar`] rather
+    /// than being ignored.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// [`from_env`](Self::from_env) with variables looked up through `var`.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let get = |name: &'static str| {
+            var(name)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .map(|value| (name, value))
+        };
+        let invalid =
+            |(name, value): (&'static str, String), reason: &str| PlantUmlError::InvalidEnvVar {
+                name,
+                value,
+                reason: reason.to_string(),
+            };
+
+        let mut options = match get("PLANTUML_RS_SECURITY_PROFILE") {
+            None => Self::new(),
+            Some((_, profile)) if profile == "default" => Self::new(),
+            Some((_, profile)) if profile == "docs" => Self::for_docs(),
+            Some((_, profile)) if profile == "ci" => Self::for_ci(),
+            Some((_, profile)) if profile == "untrusted" => Self::for_untrusted(),
+            Some(var) => return Err(invalid(var, "expected default, docs, ci or untrusted")),
+        };
+
+        if let Some(var) = get("PLANTUML_RS_TIMEOUT") {
+            let timeout = var
+                .1
+                .parse()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            match timeout {
+                Some(timeout) => options = options.timeout(timeout),
+                None => return Err(invalid(var, "expected a number of seconds")),
+            }
+        }
+        if let Some(var) = get("PLANTUML_RS_FORMAT") {
+            match var.1.parse() {
+                Ok(format) => options = options.format(format),
+                Err(e) => return Err(invalid(var, &e.to_string())),
+            }
+        }
+        if let Some((_, args)) = get("PLANTUML_RS_JVM_ARGS") {
+            for arg in args.split_whitespace() {
+                options = options.jvm_arg(arg);
+            }
+        }
+        if let Some(var) = get("PLANTUML_RS_SMETANA") {
+            match var.1.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => options = options.smetana(true),
+                "0" | "false" | "no" | "off" => options = options.smetana(false),
+                _ => return Err(invalid(var, "expected true or false")),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Output format to render; defaults to [`OutputFormat::Svg`].
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Character set PlantUML decodes the source with (`-charset`); defaults to `UTF-8`.
+    ///
+    /// String sources are always UTF-8; use this with
+    /// [`render_encoded`](crate::render_encoded) for sources in other encodings.
+    /// Names that aren't valid charset names fail the render with
+    /// [`PlantUmlError::InvalidCharset`].
+    pub fn charset(mut self, charset: impl Into<String>) -> Self {
+        self.charset = Some(charset.into());
+        self
+    }
+
+    /// Apply a theme (`!theme`) to every diagram, e.g. `"cerulean"`.
+    ///
+    /// Themes bundled with PlantUML work offline; see [`list_themes`](crate::list_themes).
+    /// Kinds that don't accept `!theme`, such as `@startjson`, are left alone.
+    pub fn theme(mut self, theme: impl Into<String>) -> Self {
+        self.theme = Some(theme.into());
+        self
+    }
+
+    /// `!include` a file at the top of every diagram, e.g. shared skinparams.
+    ///
+    /// May be called repeatedly; files are included in order. Like
+    /// [`theme`](Self::theme), this skips kinds such as `@startjson` or
+    /// `@startdot` that don't accept it.
+    pub fn include(mut self, path: impl AsRef<Path>) -> Self {
+        self.includes.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Logical file name of the source (`-filename`), used in PlantUML error
+    /// messages and metadata instead of `stdin`.
+    pub fn filename(mut self, name: impl Into<String>) -> Self {
+        self.filename = Some(name.into());
+        self
+    }
+
+    /// Render as if from the file at `path`: name it in error messages and
+    /// resolve relative `!include` targets against its directory.
+    pub(crate) fn source_file(self, path: &Path) -> Self {
+        let options = self.filename(path.display().to_string());
+
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => options.include_dir(dir),
+            _ => options,
+        }
+    }
+
+    /// Load a config file of `skinparam`/`<style>` directives before every diagram (`-config`).
+    ///
+    /// Useful for applying corporate styling centrally instead of editing each source.
+    pub fn config(mut self, path: impl AsRef<Path>) -> Self {
+        self.config = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Add a directory searched for relative `!include` targets (`plantuml.include.path`).
+    ///
+    /// Sources rendered from a string have no directory of their own, so
+    /// without this only absolute includes resolve. May be called repeatedly.
+    pub fn include_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Restrict `!include` and `!import` to files within `dirs`.
+    ///
+    /// Meant for semi-trusted input. Includes are traced in Rust before
+    /// rendering (symlinks resolved) and the render fails with
+    /// [`PlantUmlError::IncludeNotAllowed`] if any target lies outside
+    /// `dirs`, or can't be resolved statically (URLs, preprocessor
+    /// expressions). PlantUML's own `ALLOWLIST` security profile is enabled
+    /// as a second line of defense. An empty list forbids all file includes;
+    /// the bundled standard library (`<...>`) stays available.
+    pub fn include_allowlist<P: AsRef<Path>>(mut self, dirs: impl IntoIterator<Item = P>) -> Self {
+        self.include_allowlist = Some(dirs.into_iter().map(|d| d.as_ref().to_path_buf()).collect());
+        self
+    }
+
+    /// Define a preprocessor variable (`-DNAME=VALUE`) visible to `!if`/`!ifdef`.
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    /// Kill PlantUML and fail with [`PlantUmlError::Timeout`](crate::PlantUmlError::Timeout)
+    /// if rendering takes longer than `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the JVM headless and drop known-benign JVM warnings (illegal
+    /// reflective access, `Picked up _JAVA_OPTIONS`, fontconfig) from the
+    /// stderr reported in errors and logs. PlantUML's own messages are kept.
+    pub fn quiet(mut self, enabled: bool) -> Self {
+        self.quiet = enabled;
+        self
+    }
+
+    /// Lay out diagrams with PlantUML's built-in smetana engine (`-Playout=smetana`)
+    /// instead of GraphViz `dot`.
+    pub fn smetana(mut self, enabled: bool) -> Self {
+        self.smetana = enabled;
+        self
+    }
+
+    /// Retry with smetana when a diagram needs GraphViz and no `dot` is found
+    /// (enabled by default). A warning is logged through `tracing` when this happens.
+    pub fn graphviz_fallback(mut self, enabled: bool) -> Self {
+        self.no_graphviz_fallback = !enabled;
+        self
+    }
+
+    /// Retry up to `retries` times when PlantUML fails to start with a
+    /// transient error (e.g. the executable is busy) or exits before reading
+    /// the whole diagram, waiting `backoff` before the first retry and doubling the
+    /// delay each time. Diagram errors and a missing `java` are never retried.
+    ///
+    /// Useful on CI machines where the first JVM start occasionally fails
+    /// while antivirus software scans the freshly extracted cache.
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Restrict the CPU time, memory and file sizes available to PlantUML.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use plantuml::{RenderOptions, ResourceLimits};
+    ///
+    /// let options = RenderOptions::new().limits(ResourceLimits {
+    ///     cpu_time: Some(Duration::from_secs(20)),
+    ///     memory: Some(512 * 1024 * 1024),
+    ///     file_size: Some(64 * 1024 * 1024),
+    /// });
+    /// ```
+    pub fn limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Lower the scheduling priority of the JVM so background rendering doesn't
+    /// starve interactive work.
+    ///
+    /// On Unix this is the `nice` increment (1 to 19, higher is lower priority).
+    /// On Windows any positive value selects `BELOW_NORMAL_PRIORITY_CLASS`,
+    /// and 15 or more `IDLE_PRIORITY_CLASS`.
+    pub fn nice(mut self, increment: i32) -> Self {
+        self.nice = Some(increment);
+        self
+    }
+
+    /// Fail with [`PlantUmlError::OutputTooLarge`] once PlantUML writes more
+    /// than `limit` bytes of output; defaults to 256 MiB.
+    ///
+    /// Bounds the memory a broken or malicious diagram can make the host use.
+    pub fn max_stdout_bytes(mut self, limit: usize) -> Self {
+        self.max_stdout_bytes = Some(limit);
+        self
+    }
+
+    /// Reject sources larger than `limit` bytes with
+    /// [`PlantUmlError::TooComplex`] before starting the JVM.
+    ///
+    /// Protects shared services from pathological inputs; sources read from
+    /// a reader are buffered only up to the limit.
+    pub fn max_source_bytes(mut self, limit: usize) -> Self {
+        self.max_source_bytes = Some(limit);
+        self
+    }
+
+    /// Reject sources with more than `limit` lines with
+    /// [`PlantUmlError::TooComplex`] before starting the JVM.
+    pub fn max_source_lines(mut self, limit: usize) -> Self {
+        self.max_source_lines = Some(limit);
+        self
+    }
+
+    /// Keep at most `limit` bytes of PlantUML's stderr for error messages;
+    /// defaults to 1 MiB. The rest is discarded and a truncation marker
+    /// appended.
+    pub fn max_stderr_bytes(mut self, limit: usize) -> Self {
+        self.max_stderr_bytes = Some(limit);
+        self
+    }
+
+    /// Transform the rendered output, e.g. to add a watermark, inject CSS or
+    /// sanitize SVG, before it is returned or written.
+    ///
+    /// Hooks run in the order they were added, after the built-in
+    /// post-processing ([`fit`](Self::fit), [`provenance`](Self::provenance)),
+    /// and an error fails the render. They are not applied when output is
+    /// streamed with [`render_to_writer`](crate::render_to_writer).
+    ///
+    /// ```
+    /// use plantuml::RenderOptions;
+    ///
+    /// let options = RenderOptions::new().post_process(|svg| {
+    ///     let svg = String::from_utf8_lossy(&svg).replace("<svg", "<svg class=\"diagram\"");
+    ///     Ok(svg.into_bytes())
+    /// });
+    /// ```
+    pub fn post_process(
+        mut self,
+        hook: impl Fn(Vec<u8>) -> Result<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.post_processors.push(PostProcessor(Arc::new(hook)));
+        self
+    }
+
+    /// Leave the JVM running if the render is abandoned, e.g. because the
+    /// calling thread panicked. By default the process is killed and reaped.
+    ///
+    /// Timeouts and oversized output still kill the process.
+    pub fn leave_running(mut self, enabled: bool) -> Self {
+        self.leave_running = enabled;
+        self
+    }
+
+    /// Set an environment variable for the java process, e.g. `GRAPHVIZ_DOT`
+    /// or `LANG`. May be called repeatedly.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Start the java process with an empty environment, apart from variables
+    /// set with [`env`](Self::env). Keeps variables such as `_JAVA_OPTIONS`
+    /// on the host from changing how diagrams render.
+    pub fn env_clear(mut self, enabled: bool) -> Self {
+        self.env_clear = enabled;
+        self
+    }
+
+    /// Pass an extra argument to the JVM, e.g. `"-Xmx1g"` or `"-Djava.awt.headless=true"`.
+    pub fn jvm_arg(mut self, arg: impl Into<String>) -> Self {
+        self.extra_jvm_args.push(arg.into());
+        self
+    }
+
+    /// Wrap sources that lack a `@startX`/`@endX` pair, choosing the start tag
+    /// from the fragment's content (see [`wrap_fragment`](crate::wrap_fragment)).
+    pub fn auto_wrap(mut self, enabled: bool) -> Self {
+        self.auto_wrap = enabled;
+        self
+    }
+
+    /// Pin the JVM locale language (`-Duser.language`), e.g. `"en"`.
+    ///
+    /// Gantt and timing diagrams format dates with the JVM default locale,
+    /// so pinning it keeps output identical across machines.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Pin the JVM locale country (`-Duser.country`), e.g. `"US"`.
+    pub fn country(mut self, country: impl Into<String>) -> Self {
+        self.country = Some(country.into());
+        self
+    }
+
+    /// Pin the JVM time zone (`-Duser.timezone`), e.g. `"UTC"` or `"Europe/Berlin"`.
+    pub fn timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Inject a `title` into every diagram, overriding any title in the source.
+    ///
+    /// Like all injected directives, this skips kinds that don't accept
+    /// it, such as `@startjson` or `@startdot`.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Inject a `caption` below every diagram.
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    /// Inject a page `header` into every diagram.
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Inject a page `footer` into every diagram, e.g. `"generated from docs/login.puml"`.
+    pub fn footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Resize the rendered SVG to exactly `width` x `height` pixels.
+    ///
+    /// The drawing keeps its aspect ratio and is centered within the box,
+    /// so diagrams drop into fixed slide or page layouts without tuning.
+    /// Only applies to [`OutputFormat::Svg`] output.
+    pub fn fit(mut self, width: u32, height: u32) -> Self {
+        self.fit = Some((width, height));
+        self
+    }
+
+    /// Scale every diagram by `factor`, e.g. `2.0` for double-size PNGs.
+    ///
+    /// Forwarded as PlantUML's `-scale` flag, which applies to raster and
+    /// vector output alike. A `scale` directive in the source takes precedence.
+    pub fn scale(mut self, factor: f64) -> Self {
+        self.scale = Some(factor.to_string());
+        self
+    }
+
+    /// Scale every diagram to `width` pixels wide, keeping its aspect ratio.
+    pub fn scale_to_width(mut self, width: u32) -> Self {
+        self.scale = Some(format!("{} width", width));
+        self
+    }
+
+    /// Set the output resolution (`-Sdpi`), e.g. `192` for retina-quality PNGs.
+    ///
+    /// PlantUML's default is 96; only raster formats are affected.
+    pub fn dpi(mut self, dpi: u32) -> Self {
+        self.dpi = Some(dpi);
+        self
+    }
+
+    /// Embed [`Provenance`](crate::Provenance) (source and options hashes, crate and
+    /// JAR versions, render time) in SVG output, readable with
+    /// [`read_provenance`](crate::read_provenance).
+    pub fn provenance(mut self, enabled: bool) -> Self {
+        self.provenance = enabled;
+        self
+    }
+
+    /// Check options that can't be validated by their setters.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(charset) = &self.charset {
+            if !is_charset_name(charset) {
+                return Err(PlantUmlError::InvalidCharset(charset.clone()));
+            }
+        }
+
+        if cfg!(not(unix)) && (self.limits.cpu_time.is_some() || self.limits.file_size.is_some()) {
+            return Err(PlantUmlError::ResourceLimitsUnsupported);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the source must be examined before rendering, so it can't be
+    /// streamed straight into PlantUML.
+    pub(crate) fn inspects_source(&self) -> bool {
+        self.include_allowlist.is_some()
+            || self.max_source_bytes.is_some()
+            || self.max_source_lines.is_some()
+    }
+
+    /// The stdout cap in effect, see [`max_stdout_bytes`](Self::max_stdout_bytes).
+    pub(crate) fn stdout_limit(&self) -> usize {
+        self.max_stdout_bytes.unwrap_or(MAX_STDOUT_BYTES)
+    }
+
+    /// The stderr cap in effect, see [`max_stderr_bytes`](Self::max_stderr_bytes).
+    pub(crate) fn stderr_limit(&self) -> usize {
+        self.max_stderr_bytes.unwrap_or(MAX_STDERR_BYTES)
+    }
+
+    /// How PlantUML will resolve includes for this render.
+    pub(crate) fn include_options(&self) -> IncludeOptions {
+        IncludeOptions {
+            base_dir: None,
+            include_path: self.include_dirs.clone(),
+        }
+    }
+
+    /// Apply source-level options (auto-wrapping, injected directives) to `source`.
+    pub(crate) fn prepare_source<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        let source = if self.auto_wrap {
+            wrap_fragment(source)
+        } else {
+            Cow::Borrowed(source)
+        };
+
+        let preamble = self.preamble();
+        let annotations = self.annotations();
+        if preamble.is_empty() && annotations.is_empty() {
+            return source;
+        }
+
+        // Insert the directives right after every @startX line
+        let mut out = String::with_capacity(source.len() + preamble.len() + annotations.len());
+        for line in source.split_inclusive('\n') {
+            out.push_str(line);
+            if let Some(tag) = start_tag(line.trim()) {
+                if !line.ends_with('\n') {
+                    out.push('\n');
+                }
+                // Data and raw formats such as JSON or GraphViz reject these
+                if DiagramKind::from_tag(tag).accepts_directives() {
+                    out.push_str(&preamble);
+                    out.push_str(&annotations);
+                }
+            }
+        }
+
+        Cow::Owned(out)
+    }
+
+    /// Directive lines for the theme and includes.
+    fn preamble(&self) -> String {
+        let mut directives = String::new();
+
+        if let Some(theme) = &self.theme {
+            directives.push_str(&format!("!theme {}\n", theme));
+        }
+        for include in &self.includes {
+            directives.push_str(&format!("!include {}\n", include.display()));
+        }
+
+        directives
+    }
+
+    /// Directive lines for the injected title, caption, header and footer.
+    fn annotations(&self) -> String {
+        let mut directives = String::new();
+
+        for (keyword, value) in [
+            ("title", &self.title),
+            ("caption", &self.caption),
+            ("header", &self.header),
+            ("footer", &self.footer),
+        ] {
+            if let Some(value) = value {
+                // PlantUML reads a literal `\n` as a line break in these directives
+                let value = value.replace("\r\n", "\n").replace('\n', "\\n");
+                directives.push_str(&format!("{} {}\n", keyword, value));
+            }
+        }
+
+        directives
+    }
+
+    /// Arguments passed to the JVM before `-jar`.
+    pub(crate) fn jvm_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(language) = &self.language {
+            args.push(format!("-Duser.language={}", language));
+        }
+        if let Some(country) = &self.country {
+            args.push(format!("-Duser.country={}", country));
+        }
+        if let Some(timezone) = &self.timezone {
+            args.push(format!("-Duser.timezone={}", timezone));
+        }
+        if let Ok(path) = std::env::join_paths(&self.include_dirs) {
+            if !path.is_empty() {
+                args.push(format!(
+                    "-Dplantuml.include.path={}",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+        if let Some(memory) = self.limits.memory {
+            // -Xmx needs a multiple of 1 KiB
+            args.push(format!("-Xmx{}k", (memory / 1024).max(1)));
+        }
+        if self.quiet {
+            args.push("-Djava.awt.headless=true".to_string());
+        }
+        if let Some(allowlist) = &self.include_allowlist {
+            args.push("-DPLANTUML_SECURITY_PROFILE=ALLOWLIST".to_string());
+            if let Ok(path) = std::env::join_paths(allowlist) {
+                args.push(format!(
+                    "-Dplantuml.allowlist.path={}",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+        args.extend(self.extra_jvm_args.iter().cloned());
+
+        args
+    }
+
+    /// Arguments passed to PlantUML after `-jar plantuml.jar`.
+    pub(crate) fn plantuml_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-pipe".to_string(),
+            self.format.flag().to_string(),
+            "-charset".to_string(),
+            self.charset.clone().unwrap_or_else(|| "UTF-8".to_string()),
+        ];
+
+        if let Some(filename) = &self.filename {
+            args.push("-filename".to_string());
+            args.push(filename.clone());
+        }
+        if let Some(config) = &self.config {
+            args.push("-config".to_string());
+            args.push(config.display().to_string());
+        }
+        if let Some(scale) = &self.scale {
+            args.push("-scale".to_string());
+            args.push(scale.clone());
+        }
+        if let Some(dpi) = self.dpi {
+            args.push(format!("-Sdpi={}", dpi));
+        }
+        if self.smetana {
+            args.push("-Playout=smetana".to_string());
+        }
+        for (name, value) in &self.defines {
+            args.push(format!("-D{}={}", name, value));
+        }
+
+        args
+    }
+}
+
+/// Whether `name` is a legal Java charset name: an alphanumeric first
+/// character followed by alphanumerics or `-`, `+`, `.`, `:`, `_`.
+fn is_charset_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "-+.:_".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_jvm_args() {
+        assert!(RenderOptions::new().jvm_args().is_empty());
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let vars = |pairs: Vec<(&'static str, &'static str)>| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        let options = RenderOptions::from_vars(vars(vec![
+            ("PLANTUML_RS_SECURITY_PROFILE", "untrusted"),
+            ("PLANTUML_RS_TIMEOUT", "2.5"),
+            ("PLANTUML_RS_FORMAT", "PNG"),
+            ("PLANTUML_RS_JVM_ARGS", " -Xss4m  -Dfoo=bar "),
+            ("PLANTUML_RS_SMETANA", "true"),
+        ]))
+        .unwrap();
+        assert_eq!(options.timeout, Some(Duration::from_millis(2500)));
+        assert_eq!(options.format, OutputFormat::Png);
+        assert_eq!(options.extra_jvm_args, ["-Xss4m", "-Dfoo=bar"]);
+        assert!(options.smetana);
+        assert!(options.include_allowlist.is_some());
+
+        let options = RenderOptions::from_vars(vars(vec![("PLANTUML_RS_TIMEOUT", "")])).unwrap();
+        assert_eq!(options.timeout, None);
+
+        for (name, value) in [
+            ("PLANTUML_RS_SECURITY_PROFILE", "paranoid"),
+            ("PLANTUML_RS_TIMEOUT", "soon"),
+            ("PLANTUML_RS_TIMEOUT", "-1"),
+            ("PLANTUML_RS_FORMAT", "gif"),
+            ("PLANTUML_RS_SMETANA", "maybe"),
+        ] {
+            assert!(
+                matches!(
+                    RenderOptions::from_vars(vars(vec![(name, value)])),
+                    Err(PlantUmlError::InvalidEnvVar { name: n, .. }) if n == name
+                ),
+                "{}={} should be rejected",
+                name,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        let docs = RenderOptions::for_docs();
+        assert!(docs.jvm_args().contains(&"-Duser.timezone=UTC".to_string()));
+
+        let ci = RenderOptions::for_ci();
+        assert_eq!(ci.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(ci.timezone.as_deref(), Some("UTC"));
+
+        let untrusted = RenderOptions::for_untrusted();
+        assert!(untrusted.validate().is_ok());
+        assert_eq!(untrusted.include_allowlist, Some(Vec::new()));
+        assert_eq!(untrusted.stdout_limit(), 32 * 1024 * 1024);
+        assert!(untrusted.timeout.is_some());
+    }
+
+    #[test]
+    fn test_prepare_source_injects_directives() {
+        let options = RenderOptions::new()
+            .title("Login")
+            .footer("generated from login.puml\non 2024-01-01");
+        let source = "@startuml\nA -> B\n@enduml\n@startjson\n{\"a\": 1}\n@endjson\n\
+                      @startmindmap\n* root\n@endmindmap";
+
+        assert_eq!(
+            options.prepare_source(source),
+            "@startuml\ntitle Login\nfooter generated from login.puml\\non 2024-01-01\nA -> B\n@enduml\n\
+             @startjson\n{\"a\": 1}\n@endjson\n\
+             @startmindmap\ntitle Login\nfooter generated from login.puml\\non 2024-01-01\n* root\n@endmindmap"
+        );
+    }
+
+    #[test]
+    fn test_prepare_source_unchanged_by_default() {
+        let source = "@startuml\nA -> B\n@enduml";
+        assert!(matches!(
+            RenderOptions::new().prepare_source(source),
+            Cow::Borrowed(s) if s == source
+        ));
+    }
+
+    #[test]
+    fn test_plantuml_args() {
+        let options = RenderOptions::new()
+            .format(OutputFormat::Png)
+            .charset("ISO-8859-1")
+            .filename("docs/login.puml")
+            .config("corp.cfg")
+            .dpi(192)
+            .define("ENV", "prod");
+
+        assert_eq!(
+            options.plantuml_args(),
+            vec![
+                "-pipe",
+                "-tpng",
+                "-charset",
+                "ISO-8859-1",
+                "-filename",
+                "docs/login.puml",
+                "-config",
+                "corp.cfg",
+                "-Sdpi=192",
+                "-DENV=prod"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_charset() {
+        assert!(RenderOptions::new()
+            .charset("windows-1252")
+            .validate()
+            .is_ok());
+        assert!(matches!(
+            RenderOptions::new().charset("UTF-8 -tpng").validate(),
+            Err(PlantUmlError::InvalidCharset(_))
+        ));
+        assert!(RenderOptions::new().charset("").validate().is_err());
+    }
+
+    #[test]
+    fn test_prepare_source_theme_and_includes() {
+        let options = RenderOptions::new()
+            .theme("cerulean")
+            .include("styles/common.iuml");
+
+        assert_eq!(
+            options.prepare_source("@startuml\nA -> B\n@enduml"),
+            "@startuml\n!theme cerulean\n!include styles/common.iuml\nA -> B\n@enduml"
+        );
+
+        let raw = "@startyaml\na: 1\n@endyaml\n@startdot\ndigraph G { a -> b }\n@enddot\n";
+        assert_eq!(options.prepare_source(raw), raw);
+    }
+
+    #[test]
+    fn test_scale_arg() {
+        let args = RenderOptions::new().scale(2.0).plantuml_args();
+        assert!(args.ends_with(&["-scale".to_string(), "2".to_string()]));
+
+        let options = RenderOptions::new().scale(1.5).scale_to_width(800);
+        assert!(options
+            .plantuml_args()
+            .ends_with(&["-scale".to_string(), "800 width".to_string()]));
+        assert_eq!(options.preamble(), "");
+    }
+
+    #[test]
+    fn test_include_dir_jvm_arg() {
+        let options = RenderOptions::new().include_dir("styles");
+        assert_eq!(options.jvm_args(), vec!["-Dplantuml.include.path=styles"]);
+    }
+
+    #[test]
+    fn test_memory_limit_jvm_arg() {
+        let options = RenderOptions::new().limits(ResourceLimits {
+            memory: Some(256 * 1024 * 1024),
+            ..Default::default()
+        });
+        assert_eq!(options.jvm_args(), vec!["-Xmx262144k"]);
+    }
+
+    #[test]
+    fn test_locale_jvm_args() {
+        let options = RenderOptions::new()
+            .language("de")
+            .country("DE")
+            .timezone("UTC");
+
+        assert_eq!(
+            options.jvm_args(),
+            vec![
+                "-Duser.language=de",
+                "-Duser.country=DE",
+                "-Duser.timezone=UTC"
+            ]
+        );
+    }
+}
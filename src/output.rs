@@ -0,0 +1,81 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{PlantUmlError, Result};
+
+/// Distinguishes temp files written concurrently by the same process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Write `contents` to `path` atomically.
+///
+/// The data is written to a temporary file in the same directory and renamed
+/// over `path`, so readers see either the old file or the complete new one,
+/// never a truncated file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let output_err = |source| PlantUmlError::OutputWrite {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let temp = temp_path(path);
+    let result = fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(output_err(e));
+    }
+
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let unique = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), unique))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces() {
+        let dir = std::env::temp_dir().join(format!("plantuml-rs-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.svg");
+
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_dir(&dir).unwrap().count(),
+            1,
+            "No temp files left behind"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_write_atomic_missing_dir() {
+        let path = std::env::temp_dir()
+            .join("plantuml-rs-no-such-dir")
+            .join("out.svg");
+        assert!(matches!(
+            write_atomic(&path, b"data"),
+            Err(PlantUmlError::OutputWrite { .. })
+        ));
+    }
+}
@@ -0,0 +1,1231 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::process::ChildStdin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{PlantUmlError, Result};
+use crate::executor::{check_input, command, exit_error, read_stderr, strip_jvm_noise, ChildGuard};
+use crate::inspect::blocks;
+use crate::options::RenderOptions;
+
+/// Line PlantUML writes after each diagram in pipe mode
+const DELIMITER: &str = "--plantuml-rs-end-of-diagram--";
+
+/// A long-lived PlantUML process that renders many diagrams.
+///
+/// Every [`render`](crate::render) call pays for a JVM startup, which
+/// usually dominates the render time. A `Renderer` starts PlantUML once in
+/// `-pipe` mode and feeds it one source after another, reading each
+/// diagram back up to a delimiter line.
+///
+/// # Syntax errors are not `Err`
+///
+/// Unlike [`render`](crate::render), a syntax error does **not** fail the
+/// render with [`PlantUmlError::SyntaxError`]. PlantUML keeps the process
+/// running and returns its error diagram as a successful render, so check
+/// sources beforehand or inspect the output if that matters.
+///
+/// # Process lifetime
+///
+/// The process is started on the first render and restarted transparently
+/// if it has exited. Any failure (timeout, I/O error, oversized output)
+/// kills it, so the next render starts from a clean JVM. It is killed when
+/// the `Renderer` is dropped. Renders are never retried or re-run with
+/// smetana, and resource limits apply to the process as a whole.
+///
+/// # Example
+///
+/// ```no_run
+/// let mut renderer = plantuml::Renderer::new();
+/// for name in ["Alice", "Bob"] {
+///     let svg = renderer
+///         .render(&format!("@startuml\n{name} -> Carol\n@enduml"))
+///         .unwrap();
+/// }
+/// ```
+pub struct Renderer {
+    options: RenderOptions,
+    process: Option<Process>,
+    last_usage: Option<RenderUsage>,
+    /// The owning pool's process counts, if pooled
+    counts: Option<Arc<ProcessCounts>>,
+}
+
+/// Resources a [`Renderer`]'s process used for one render.
+///
+/// Best-effort: CPU time and memory are sampled from the process before
+/// and after the render, so JVM background work such as garbage collection
+/// counts towards whichever render it overlaps. They are `None` where the
+/// platform doesn't expose them, which is currently everywhere but Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderUsage {
+    /// Time from sending the source to receiving the last diagram.
+    pub wall_time: Duration,
+    /// User plus system CPU time the process spent during the render.
+    pub cpu_time: Option<Duration>,
+    /// Resident memory of the process after the render, in bytes.
+    pub resident_bytes: Option<u64>,
+    /// How much the resident memory grew during the render, in bytes.
+    pub resident_growth: Option<u64>,
+}
+
+/// CPU time and resident memory of a process at one point in time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProcessSample {
+    pub(crate) cpu_time: Duration,
+    pub(crate) resident_bytes: u64,
+}
+
+/// A running PlantUML process and the pipes to talk to it.
+struct Process {
+    child: ChildGuard,
+    sources: Sender<Vec<u8>>,
+    images: Receiver<Result<Vec<u8>>>,
+    stderr: Option<thread::JoinHandle<String>>,
+    /// Set if writing a source to stdin failed
+    write_error: Arc<Mutex<Option<std::io::Error>>>,
+    /// Declared after `child`, so it counts the process once it is reaped
+    _tracker: Option<ProcessTracker>,
+}
+
+/// Processes started and stopped by the workers of one [`RendererPool`].
+#[derive(Debug, Default)]
+struct ProcessCounts {
+    started: AtomicUsize,
+    stopped: AtomicUsize,
+}
+
+/// Counts its process as stopped when dropped.
+struct ProcessTracker(Arc<ProcessCounts>);
+
+impl ProcessTracker {
+    fn start(counts: &Arc<ProcessCounts>) -> Self {
+        counts.started.fetch_add(1, Ordering::SeqCst);
+        Self(Arc::clone(counts))
+    }
+}
+
+impl Drop for ProcessTracker {
+    fn drop(&mut self) {
+        self.0.stopped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Renderer {
+    /// Create a renderer producing SVG with default options.
+    pub fn new() -> Self {
+        Self::with_options(RenderOptions::new())
+    }
+
+    /// Create a renderer that applies `options` to every render.
+    ///
+    /// The options are fixed for the lifetime of the process; the timeout
+    /// applies to each render separately.
+    pub fn with_options(options: RenderOptions) -> Self {
+        Self {
+            options,
+            process: None,
+            last_usage: None,
+            counts: None,
+        }
+    }
+
+    /// A worker for a pool, counting its processes in `counts`.
+    fn pooled(options: RenderOptions, counts: &Arc<ProcessCounts>) -> Self {
+        Self {
+            counts: Some(Arc::clone(counts)),
+            ..Self::with_options(options)
+        }
+    }
+
+    /// Render PlantUML syntax and return the raw output bytes.
+    ///
+    /// A source with several diagrams returns their outputs concatenated,
+    /// as [`render_with_options`](crate::render_with_options) does. A
+    /// diagram with a syntax error comes back as PlantUML's error image,
+    /// not as an `Err`; see the [type docs](Renderer).
+    pub fn render(&mut self, plantuml: &str) -> Result<Vec<u8>> {
+        let source = self.options.prepare_source(plantuml);
+        let count = diagram_count(&source)?;
+        check_input(source.as_bytes(), &self.options)?;
+
+        self.last_usage = None;
+        if !self.is_running() {
+            self.process = Some(Process::spawn(&self.options, self.counts.as_ref())?);
+        }
+
+        let started = Instant::now();
+        let before = self.sample();
+        let output = self.render_source(&source, count);
+        let usage = self.usage_since(started, before);
+        tracing::debug!(
+            wall_ms = usage.wall_time.as_millis() as u64,
+            cpu_ms = usage.cpu_time.map(|t| t.as_millis() as u64),
+            resident_bytes = usage.resident_bytes,
+            "render usage"
+        );
+        self.last_usage = Some(usage);
+
+        if output.is_err() {
+            // Drop the process so the next render starts from a clean JVM
+            self.process = None;
+        }
+
+        crate::postprocess(output?, Some(plantuml), &self.options)
+    }
+
+    /// Resources used by the most recent render, whether it succeeded or not.
+    ///
+    /// `None` before the first render, or if the last one failed before
+    /// reaching the process. See [`RenderUsage`] for its accuracy.
+    pub fn last_usage(&self) -> Option<RenderUsage> {
+        self.last_usage
+    }
+
+    /// Kill the process, as if it had crashed.
+    #[cfg(test)]
+    pub(crate) fn kill_process(&mut self) {
+        if let Some(process) = &mut self.process {
+            process.child.kill().unwrap();
+            process.child.wait().unwrap();
+        }
+    }
+
+    /// Whether a PlantUML process is currently running.
+    pub fn is_running(&mut self) -> bool {
+        match &mut self.process {
+            Some(process) => matches!(process.child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+
+    fn sample(&self) -> Option<ProcessSample> {
+        sample_process(self.process.as_ref()?.child.id())
+    }
+
+    fn usage_since(&self, started: Instant, before: Option<ProcessSample>) -> RenderUsage {
+        let after = self.sample();
+        let both = before.zip(after);
+
+        RenderUsage {
+            wall_time: started.elapsed(),
+            cpu_time: both.map(|(before, after)| after.cpu_time.saturating_sub(before.cpu_time)),
+            resident_bytes: after.map(|after| after.resident_bytes),
+            resident_growth: both
+                .map(|(before, after)| after.resident_bytes.saturating_sub(before.resident_bytes)),
+        }
+    }
+
+    fn render_source(&mut self, source: &str, count: usize) -> Result<Vec<u8>> {
+        let process = self.process.as_mut().unwrap();
+
+        // Written on the process's own thread, so a wedged JVM is caught by the
+        // timeout below; if that thread is gone, the process is exiting too
+        let _ = process.sources.send(format!("{}\n", source).into_bytes());
+
+        let deadline = self.options.timeout.map(|t| (t, Instant::now() + t));
+        let mut output = Vec::new();
+
+        for _ in 0..count {
+            let image = match deadline {
+                Some((timeout, deadline)) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match process.images.recv_timeout(remaining) {
+                        Err(RecvTimeoutError::Timeout) => {
+                            return Err(PlantUmlError::Timeout(timeout))
+                        }
+                        image => image.ok(),
+                    }
+                }
+                None => process.images.recv().ok(),
+            };
+
+            match image {
+                Some(image) => output.extend(image?),
+                None => return Err(process.exit_error(self.options.quiet)),
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pool of [`Renderer`]s for rendering in parallel.
+///
+/// Each render checks out an idle worker. If all are busy, the pool grows
+/// by one worker up to [`max_workers`](PoolOptions::max_workers), and once
+/// it can't grow, the render waits for a worker to be returned, so at most
+/// `max_workers` JVMs run at once. Workers start their process on first
+/// use, and a worker whose process crashed or was killed starts a fresh one
+/// on its next render. With an [`idle_timeout`](PoolOptions::idle_timeout),
+/// workers above [`min_workers`](PoolOptions::min_workers) that stay idle
+/// that long are stopped, so a quiet server doesn't keep idle JVMs around.
+/// Concurrent renders of the same source are coalesced into one, so a hot
+/// diagram doesn't occupy every worker.
+///
+/// # Example
+///
+/// ```no_run
+/// let pool = plantuml::RendererPool::new(4);
+/// let sources = vec!["@startuml\nA -> B\n@enduml"; 100];
+///
+/// std::thread::scope(|s| {
+///     for source in &sources {
+///         s.spawn(|| pool.render(source).unwrap());
+///     }
+/// });
+/// ```
+pub struct RendererPool {
+    shared: Arc<Shared>,
+}
+
+/// Sizing of a [`RendererPool`], see [`RendererPool::with_pool_options`].
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// Workers kept even when idle.
+    pub min_workers: usize,
+    /// Maximum number of workers, and so of JVMs running at once.
+    pub max_workers: usize,
+    /// How long a worker above `min_workers` may stay idle before it and its
+    /// JVM are stopped. `None` keeps every worker once started.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            min_workers: 1,
+            max_workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            idle_timeout: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// How urgently a render on a [`RendererPool`] needs a worker.
+///
+/// When workers are scarce, a returned worker goes to a waiting
+/// [`Interactive`](Self::Interactive) render before any
+/// [`Batch`](Self::Batch) render, so an editor preview isn't queued behind
+/// a docs build sharing the pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Someone is waiting on the result, such as an editor preview.
+    #[default]
+    Interactive,
+    /// Background work, such as rendering a whole docs tree.
+    Batch,
+}
+
+/// Current size of a [`RendererPool`], see [`RendererPool::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Workers in the pool, idle or busy.
+    pub workers: usize,
+    /// Workers waiting for a render.
+    pub idle: usize,
+    /// Interactive renders waiting for a worker.
+    pub waiting_interactive: usize,
+    /// Batch renders waiting for a worker.
+    pub waiting_batch: usize,
+    /// PlantUML processes the pool's workers have started.
+    pub processes_started: usize,
+    /// Of those, processes that have since been stopped and reaped.
+    pub processes_stopped: usize,
+}
+
+/// State shared between a pool, its checked out workers and its reaper thread.
+struct Shared {
+    options: RenderOptions,
+    pool_options: PoolOptions,
+    state: Mutex<PoolState>,
+    /// Signalled when a worker is returned or the pool is dropped
+    returned: Condvar,
+    /// Renders in progress, by source, for callers to join
+    in_flight: Mutex<HashMap<String, Arc<Flight>>>,
+    counts: Arc<ProcessCounts>,
+    /// Renders actually run by `render_with_priority`, shared or not
+    #[cfg(test)]
+    renders: AtomicUsize,
+}
+
+struct PoolState {
+    /// Idle workers with the time they were returned, oldest first
+    idle: Vec<(Renderer, Instant)>,
+    /// All workers, idle or checked out
+    workers: usize,
+    /// Checkouts blocked waiting for a worker, by priority
+    waiting_interactive: usize,
+    waiting_batch: usize,
+    /// Set when the pool is dropped, to stop the reaper
+    closed: bool,
+}
+
+impl PoolState {
+    fn waiting(&mut self, priority: Priority) -> &mut usize {
+        match priority {
+            Priority::Interactive => &mut self.waiting_interactive,
+            Priority::Batch => &mut self.waiting_batch,
+        }
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A render shared by every caller that asked for the same source meanwhile.
+struct Flight {
+    /// Priority of the leader, which callers of a higher priority won't wait on
+    priority: Priority,
+    /// `None` while rendering, then the output, or `Some(None)` if it failed
+    output: Mutex<Option<Option<Vec<u8>>>>,
+    done: Condvar,
+}
+
+impl Flight {
+    fn new(priority: Priority) -> Self {
+        Self {
+            priority,
+            output: Mutex::new(None),
+            done: Condvar::new(),
+        }
+    }
+
+    fn finish(&self, output: Option<Vec<u8>>) {
+        *self.output.lock().unwrap_or_else(|e| e.into_inner()) = Some(output);
+        self.done.notify_all();
+    }
+
+    fn wait(&self) -> Option<Vec<u8>> {
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(output) = &*output {
+                return output.clone();
+            }
+            output = self.done.wait(output).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Publishes the leader's result to a [`Flight`], even if the render panics.
+struct Leader<'a> {
+    pool: &'a RendererPool,
+    source: &'a str,
+    flight: Arc<Flight>,
+    output: Option<Vec<u8>>,
+    joinable: bool,
+}
+
+impl Leader<'_> {
+    /// Stop others from joining; returns whether anyone did.
+    fn close(&mut self) -> bool {
+        if self.joinable {
+            let mut in_flight = self
+                .pool
+                .shared
+                .in_flight
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            // An interactive leader may have replaced a batch flight
+            if in_flight
+                .get(self.source)
+                .is_some_and(|flight| Arc::ptr_eq(flight, &self.flight))
+            {
+                in_flight.remove(self.source);
+            }
+            self.joinable = false;
+        }
+
+        Arc::strong_count(&self.flight) > 1
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.close();
+        self.flight.finish(self.output.take());
+    }
+}
+
+impl RendererPool {
+    /// Create a pool of `size` SVG renderers with default options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        Self::with_options(size, RenderOptions::new())
+    }
+
+    /// Create a pool of `size` renderers that apply `options` to every render.
+    ///
+    /// The pool keeps all `size` workers; use
+    /// [`with_pool_options`](Self::with_pool_options) for one that scales.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn with_options(size: usize, options: RenderOptions) -> Self {
+        let pool_options = PoolOptions {
+            min_workers: size,
+            max_workers: size,
+            idle_timeout: None,
+        };
+
+        Self::with_pool_options(pool_options, options)
+    }
+
+    /// Create a pool sized by `pool_options` whose renderers apply `options`
+    /// to every render.
+    ///
+    /// With an idle timeout, a background thread stops workers that have
+    /// been idle too long; it exits when the pool is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_workers` is zero or less than `min_workers`.
+    pub fn with_pool_options(pool_options: PoolOptions, options: RenderOptions) -> Self {
+        assert!(
+            pool_options.max_workers > 0,
+            "renderer pool size must be at least 1"
+        );
+        assert!(
+            pool_options.min_workers <= pool_options.max_workers,
+            "renderer pool min_workers must not exceed max_workers"
+        );
+
+        let now = Instant::now();
+        let counts = Arc::new(ProcessCounts::default());
+        let idle = (0..pool_options.min_workers)
+            .map(|_| (Renderer::pooled(options.clone(), &counts), now))
+            .collect();
+        let shared = Arc::new(Shared {
+            options,
+            state: Mutex::new(PoolState {
+                idle,
+                workers: pool_options.min_workers,
+                waiting_interactive: 0,
+                waiting_batch: 0,
+                closed: false,
+            }),
+            returned: Condvar::new(),
+            in_flight: Mutex::new(HashMap::new()),
+            counts,
+            #[cfg(test)]
+            renders: AtomicUsize::new(0),
+            pool_options,
+        });
+
+        let scales = shared.pool_options.min_workers < shared.pool_options.max_workers;
+        if let Some(timeout) = shared.pool_options.idle_timeout.filter(|_| scales) {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || reap_idle(&shared, timeout));
+        }
+
+        Self { shared }
+    }
+
+    /// Render PlantUML syntax on the next idle worker.
+    ///
+    /// Concurrent calls with the same source share a single render: later
+    /// callers wait for the first and get a copy of its output. Errors
+    /// can't be shared, so if the shared render fails, each waiting caller
+    /// renders on its own to get its error.
+    pub fn render(&self, plantuml: &str) -> Result<Vec<u8>> {
+        self.render_with_priority(plantuml, Priority::Interactive)
+    }
+
+    /// Render PlantUML syntax, waiting for a worker at `priority`.
+    ///
+    /// Like [`render`](Self::render), but an interactive render doesn't
+    /// join a batch render of the same source, which may still be queued.
+    pub fn render_with_priority(&self, plantuml: &str, priority: Priority) -> Result<Vec<u8>> {
+        let mut in_flight = self
+            .shared
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let joinable = in_flight
+            .get(plantuml)
+            .filter(|flight| {
+                flight.priority == Priority::Interactive || priority == Priority::Batch
+            })
+            .cloned();
+        if let Some(flight) = joinable {
+            drop(in_flight);
+            return match flight.wait() {
+                Some(output) => Ok(output),
+                None => self.render_alone(plantuml, priority),
+            };
+        }
+
+        let flight = Arc::new(Flight::new(priority));
+        in_flight.insert(plantuml.to_string(), Arc::clone(&flight));
+        drop(in_flight);
+
+        let mut leader = Leader {
+            pool: self,
+            source: plantuml,
+            flight,
+            output: None,
+            joinable: true,
+        };
+        let result = self.render_alone(plantuml, priority);
+        // Only copy the output if someone joined
+        if let Ok(output) = &result {
+            if leader.close() {
+                leader.output = Some(output.clone());
+            }
+        }
+        drop(leader);
+
+        result
+    }
+
+    fn render_alone(&self, plantuml: &str, priority: Priority) -> Result<Vec<u8>> {
+        #[cfg(test)]
+        self.shared.renders.fetch_add(1, Ordering::SeqCst);
+
+        self.checkout_with_priority(priority).render(plantuml)
+    }
+
+    /// Take an idle worker, adding one if all are busy and the pool can
+    /// still grow, or else blocking until one is returned.
+    ///
+    /// The worker goes back to the pool when the guard is dropped.
+    pub fn checkout(&self) -> PooledRenderer<'_> {
+        self.checkout_with_priority(Priority::Interactive)
+    }
+
+    /// Take a worker like [`checkout`](Self::checkout), but while any
+    /// interactive checkout is waiting, a batch checkout leaves free
+    /// workers to it.
+    pub fn checkout_with_priority(&self, priority: Priority) -> PooledRenderer<'_> {
+        let shared = &self.shared;
+        let mut state = shared.state();
+        let mut waiting = false;
+        loop {
+            let yields = priority == Priority::Batch && state.waiting_interactive > 0;
+            if !yields {
+                let renderer = match state.idle.pop() {
+                    Some((renderer, _)) => Some(renderer),
+                    None if state.workers < shared.pool_options.max_workers => {
+                        state.workers += 1;
+                        tracing::debug!(workers = state.workers, "growing renderer pool");
+                        Some(Renderer::pooled(shared.options.clone(), &shared.counts))
+                    }
+                    None => None,
+                };
+                if let Some(renderer) = renderer {
+                    if waiting {
+                        *state.waiting(priority) -= 1;
+                        if priority == Priority::Interactive {
+                            // Batch checkouts may be yielding to this one
+                            shared.returned.notify_all();
+                        }
+                    }
+                    return PooledRenderer {
+                        pool: self,
+                        renderer: Some(renderer),
+                    };
+                }
+            }
+            if !waiting {
+                *state.waiting(priority) += 1;
+                waiting = true;
+            }
+            state = shared
+                .returned
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Render on the next idle worker and also return the resources the
+    /// render used, see [`RenderUsage`].
+    ///
+    /// Unlike [`render`](Self::render), identical concurrent renders are not
+    /// coalesced, so the usage always belongs to this source.
+    pub fn render_with_usage(
+        &self,
+        plantuml: &str,
+        priority: Priority,
+    ) -> Result<(Vec<u8>, RenderUsage)> {
+        let mut worker = self.checkout_with_priority(priority);
+        let output = worker.render(plantuml)?;
+
+        Ok((output, worker.last_usage().unwrap_or_default()))
+    }
+
+    /// The pool's current number of workers.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.shared.state();
+
+        PoolStats {
+            workers: state.workers,
+            idle: state.idle.len(),
+            waiting_interactive: state.waiting_interactive,
+            waiting_batch: state.waiting_batch,
+            processes_started: self.shared.counts.started.load(Ordering::SeqCst),
+            processes_stopped: self.shared.counts.stopped.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Number of idle workers holding a process, running or not.
+    pub(crate) fn idle_processes(&self) -> usize {
+        let state = self.shared.state();
+        state
+            .idle
+            .iter()
+            .filter(|(renderer, _)| renderer.process.is_some())
+            .count()
+    }
+}
+
+impl Drop for RendererPool {
+    fn drop(&mut self) {
+        let idle = {
+            let mut state = self.shared.state();
+            state.closed = true;
+            std::mem::take(&mut state.idle)
+        };
+        self.shared.returned.notify_all();
+
+        // Stop the idle JVMs now rather than whenever the reaper lets go
+        drop(idle);
+    }
+}
+
+/// Stop workers above the minimum once they have been idle for `timeout`,
+/// until the pool is dropped.
+fn reap_idle(shared: &Shared, timeout: Duration) {
+    let min_workers = shared.pool_options.min_workers;
+    let mut state = shared.state();
+
+    while !state.closed {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        while state.workers > min_workers {
+            match state.idle.first() {
+                Some((_, since)) if now.duration_since(*since) >= timeout => {
+                    expired.push(state.idle.remove(0).0);
+                    state.workers -= 1;
+                }
+                _ => break,
+            }
+        }
+
+        if !expired.is_empty() {
+            tracing::debug!(
+                stopped = expired.len(),
+                workers = state.workers,
+                "shrinking renderer pool"
+            );
+            // Kill the JVMs without holding up renders
+            drop(state);
+            drop(expired);
+            state = shared.state();
+            continue;
+        }
+
+        let wait = match state.idle.first() {
+            Some((_, since)) if state.workers > min_workers => {
+                (*since + timeout).saturating_duration_since(now)
+            }
+            _ => timeout,
+        };
+        state = shared
+            .returned
+            .wait_timeout(state, wait)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
+
+/// A [`Renderer`] checked out of a [`RendererPool`].
+pub struct PooledRenderer<'a> {
+    pool: &'a RendererPool,
+    renderer: Option<Renderer>,
+}
+
+impl Deref for PooledRenderer<'_> {
+    type Target = Renderer;
+
+    fn deref(&self) -> &Renderer {
+        self.renderer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledRenderer<'_> {
+    fn deref_mut(&mut self) -> &mut Renderer {
+        self.renderer.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledRenderer<'_> {
+    fn drop(&mut self) {
+        if let Some(renderer) = self.renderer.take() {
+            let shared = &self.pool.shared;
+            shared.state().idle.push((renderer, Instant::now()));
+            // Wakes the reaper as well, which is cheap
+            shared.returned.notify_all();
+        }
+    }
+}
+
+impl Process {
+    fn spawn(options: &RenderOptions, counts: Option<&Arc<ProcessCounts>>) -> Result<Self> {
+        let mut command = command(options)?;
+        command.args(["-pipedelimitor", DELIMITER]);
+
+        tracing::debug!(command = ?command, "spawning persistent plantuml");
+
+        let mut child = command.spawn().map_err(PlantUmlError::ProcessSpawn)?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+
+        let (sender, images) = mpsc::channel();
+        let limit = options.stdout_limit();
+        thread::spawn(move || read_images(stdout, sender, limit));
+
+        let (sources, pending) = mpsc::channel();
+        let write_error = Arc::new(Mutex::new(None));
+        let error = Arc::clone(&write_error);
+        thread::spawn(move || {
+            if let Err(e) = write_sources(stdin, pending) {
+                *error.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            }
+        });
+
+        Ok(Self {
+            child: ChildGuard::new(child, true),
+            sources,
+            images,
+            stderr: Some(read_stderr(stderr, options.stderr_limit())),
+            write_error,
+            _tracker: counts.map(ProcessTracker::start),
+        })
+    }
+
+    /// The error for a process that stopped producing output.
+    fn exit_error(&mut self, quiet: bool) -> PlantUmlError {
+        let status = match self.child.wait() {
+            Ok(status) => status,
+            Err(e) => return PlantUmlError::ProcessSpawn(e),
+        };
+        let mut stderr = self
+            .stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+        if quiet {
+            stderr = strip_jvm_noise(&stderr);
+        }
+
+        // A failed write closed stdin, which is why PlantUML stopped
+        let write_error = self
+            .write_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(e) = write_error.filter(|e| e.kind() != std::io::ErrorKind::BrokenPipe) {
+            return PlantUmlError::StdinWrite(e);
+        }
+
+        if status.success() {
+            return PlantUmlError::ProcessFailed { code: 0, stderr };
+        }
+
+        exit_error(status, stderr)
+    }
+}
+
+/// Sample a process's CPU time and resident memory from `/proc`.
+#[cfg(target_os = "linux")]
+pub(crate) fn sample_process(pid: u32) -> Option<ProcessSample> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the command name, which is in parentheses and may contain
+    // spaces; the first of these is field 3 of proc(5)
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+    let ticks = field(14)? + field(15)?;
+    let pages = field(24)?;
+
+    let tick_rate = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if tick_rate <= 0 || page_size <= 0 {
+        return None;
+    }
+
+    Some(ProcessSample {
+        cpu_time: Duration::from_secs_f64(ticks as f64 / tick_rate as f64),
+        resident_bytes: pages * page_size as u64,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn sample_process(_pid: u32) -> Option<ProcessSample> {
+    None
+}
+
+/// Write each queued source to stdin until the renderer goes away or a write fails.
+fn write_sources(mut stdin: ChildStdin, pending: Receiver<Vec<u8>>) -> std::io::Result<()> {
+    for source in pending {
+        stdin.write_all(&source)?;
+        stdin.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Split PlantUML's stdout into diagrams at each delimiter line.
+///
+/// Fails once a diagram exceeds `limit` bytes. Every read is bounded, so
+/// binary output without newlines can't grow past the limit either.
+fn read_images(stdout: impl Read, sender: Sender<Result<Vec<u8>>>, limit: usize) {
+    // Room for the delimiter line after a diagram of exactly `limit` bytes
+    let max_len = limit + DELIMITER.len() + "\r\n".len();
+    let mut reader = BufReader::new(stdout);
+    let mut image = Vec::new();
+
+    loop {
+        let remaining = (max_len + 1 - image.len()) as u64;
+        match (&mut reader).take(remaining).read_until(b'\n', &mut image) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                let _ = sender.send(Err(PlantUmlError::ProcessSpawn(e)));
+                return;
+            }
+        }
+
+        match strip_delimiter(&image) {
+            Some(len) if len <= limit => {
+                image.truncate(len);
+                if sender.send(Ok(std::mem::take(&mut image))).is_err() {
+                    return;
+                }
+            }
+            None if image.len() <= limit => {}
+            _ => {
+                let _ = sender.send(Err(PlantUmlError::OutputTooLarge { limit }));
+                return;
+            }
+        }
+    }
+}
+
+/// Length of `buf` without a trailing delimiter line, if it ends with one.
+fn strip_delimiter(buf: &[u8]) -> Option<usize> {
+    let line = buf.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+    line.strip_suffix(DELIMITER.as_bytes())
+        .map(|image| image.len())
+}
+
+/// Number of diagrams PlantUML will emit for `source`.
+///
+/// Rejects sources it would wait on forever: without any diagram, or with
+/// one that is never closed.
+fn diagram_count(source: &str) -> Result<usize> {
+    let blocks = blocks(source);
+
+    if let Some((info, _)) = blocks.iter().find(|(_, text)| {
+        let last = text.lines().last().unwrap_or_default();
+        !last.trim().starts_with("@end")
+    }) {
+        return Err(PlantUmlError::SyntaxError(format!(
+            "{} at line {} is never closed",
+            info.kind, info.start_line
+        )));
+    }
+
+    if blocks.is_empty() {
+        return Err(PlantUmlError::SyntaxError(
+            "no @startuml ... @enduml block found".to_string(),
+        ));
+    }
+
+    Ok(blocks.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::OutputFormat;
+
+    #[test]
+    fn test_renderer_reuses_process() {
+        let mut renderer = Renderer::new();
+
+        let first = renderer.render("@startuml\nAlice -> Bob\n@enduml").unwrap();
+        let pid = renderer.process.as_ref().unwrap().child.id();
+        let second = renderer.render("@startuml\nBob -> Carol\n@enduml").unwrap();
+
+        assert!(String::from_utf8(first).unwrap().contains("Alice"));
+        assert!(String::from_utf8(second).unwrap().contains("Carol"));
+        assert_eq!(renderer.process.as_ref().unwrap().child.id(), pid);
+    }
+
+    #[test]
+    fn test_renderer_usage() {
+        let mut renderer = Renderer::new();
+        assert_eq!(renderer.last_usage(), None);
+
+        renderer.render("@startuml\nAlice -> Bob\n@enduml").unwrap();
+        let usage = renderer.last_usage().unwrap();
+        assert!(usage.wall_time > Duration::ZERO);
+        if cfg!(target_os = "linux") {
+            assert!(usage.cpu_time.is_some());
+            assert!(usage.resident_bytes.is_some_and(|bytes| bytes > 0));
+            assert!(usage.resident_growth.is_some());
+        }
+
+        let pool = RendererPool::new(1);
+        let (svg, usage) = pool
+            .render_with_usage("@startuml\nAlice -> Bob\n@enduml", Priority::Batch)
+            .unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("Alice"));
+        assert!(usage.wall_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_renderer_binary_output() {
+        let options = RenderOptions::new()
+            .format(OutputFormat::Png)
+            .timeout(Duration::from_secs(60));
+        let mut renderer = Renderer::with_options(options);
+
+        for _ in 0..2 {
+            let png = renderer.render("@startuml\nA -> B\n@enduml").unwrap();
+            assert!(png.starts_with(b"\x89PNG"), "Output should be a PNG");
+        }
+    }
+
+    #[test]
+    fn test_renderer_output_limit() {
+        let options = RenderOptions::new()
+            .format(OutputFormat::Png)
+            .max_stdout_bytes(16);
+        let mut renderer = Renderer::with_options(options);
+
+        let result = renderer.render("@startuml\nA -> B\n@enduml");
+        assert!(
+            matches!(result, Err(PlantUmlError::OutputTooLarge { limit: 16 })),
+            "Oversized output should fail: {:?}",
+            result.map(|png| png.len())
+        );
+    }
+
+    #[test]
+    fn test_read_images_caps_output_without_newlines() {
+        // Endless output without a newline must not be buffered forever
+        let (sender, images) = mpsc::channel();
+        read_images(std::io::repeat(0x89), sender, 1024);
+
+        assert!(matches!(
+            images.recv().unwrap(),
+            Err(PlantUmlError::OutputTooLarge { limit: 1024 })
+        ));
+    }
+
+    #[test]
+    fn test_renderer_restarts_killed_process() {
+        let mut renderer = Renderer::new();
+        renderer.render("@startuml\nA -> B\n@enduml").unwrap();
+
+        let process = renderer.process.as_mut().unwrap();
+        process.child.kill().unwrap();
+        process.child.wait().unwrap();
+
+        assert!(!renderer.is_running());
+        assert!(renderer.render("@startuml\nA -> B\n@enduml").is_ok());
+    }
+
+    #[test]
+    fn test_pool_renders_in_parallel() {
+        let pool = RendererPool::new(2);
+        let sources: Vec<String> = (0..4)
+            .map(|i| format!("@startuml\nA -> B{}\n@enduml", i))
+            .collect();
+
+        std::thread::scope(|s| {
+            for (i, source) in sources.iter().enumerate() {
+                let pool = &pool;
+                s.spawn(move || {
+                    let svg = String::from_utf8(pool.render(source).unwrap()).unwrap();
+                    assert!(svg.contains(&format!("B{}", i)));
+                });
+            }
+        });
+
+        assert_eq!(pool.stats().idle, 2);
+    }
+
+    #[test]
+    fn test_pool_shares_identical_renders() {
+        let pool = RendererPool::new(1);
+        let source = "@startuml\nShared -> Render\n@enduml";
+        // References to the source's flight: the map's, the leader's and
+        // one per caller that joined it
+        let flight_refs = || {
+            let in_flight = pool.shared.in_flight.lock().unwrap();
+            in_flight.get(source).map_or(0, Arc::strong_count)
+        };
+        let wait_for_refs = |refs: usize| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while flight_refs() < refs {
+                assert!(Instant::now() < deadline, "render never joined the flight");
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        // Hold the only worker so the first render waits in the flight
+        // until the second has joined it
+        let worker = pool.checkout();
+        let (first, second) = std::thread::scope(|s| {
+            let first = s.spawn(|| pool.render(source).unwrap());
+            wait_for_refs(2);
+            let second = s.spawn(|| pool.render(source).unwrap());
+            wait_for_refs(3);
+            drop(worker);
+
+            (first.join().unwrap(), second.join().unwrap())
+        });
+
+        assert_eq!(first, second);
+        assert_eq!(pool.shared.renders.load(Ordering::SeqCst), 1);
+        assert!(pool.shared.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pool_grows_and_shrinks() {
+        let pool_options = PoolOptions {
+            min_workers: 1,
+            max_workers: 3,
+            idle_timeout: Some(Duration::from_millis(100)),
+        };
+        let pool = RendererPool::with_pool_options(pool_options, RenderOptions::new());
+        let size = |pool: &RendererPool| {
+            let stats = pool.stats();
+            (stats.workers, stats.idle)
+        };
+        assert_eq!(size(&pool), (1, 1));
+
+        let workers: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+        assert_eq!(size(&pool), (3, 0));
+        drop(workers);
+        assert_eq!(pool.stats().idle, 3);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.stats().workers > 1 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(size(&pool), (1, 1));
+    }
+
+    #[test]
+    fn test_pool_serves_interactive_before_batch() {
+        let pool = RendererPool::new(1);
+        let worker = pool.checkout();
+        let order = Mutex::new(Vec::new());
+
+        thread::scope(|s| {
+            let wait_for = |stats: fn(PoolStats) -> bool| {
+                while !stats(pool.stats()) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+            };
+            s.spawn(|| {
+                let _worker = pool.checkout_with_priority(Priority::Batch);
+                order.lock().unwrap().push(Priority::Batch);
+            });
+            wait_for(|stats| stats.waiting_batch == 1);
+            s.spawn(|| {
+                let _worker = pool.checkout_with_priority(Priority::Interactive);
+                order.lock().unwrap().push(Priority::Interactive);
+            });
+            wait_for(|stats| stats.waiting_interactive == 1);
+            drop(worker);
+        });
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            [Priority::Interactive, Priority::Batch]
+        );
+        assert_eq!(pool.stats().idle, 1);
+    }
+
+    #[test]
+    fn test_pool_restarts_crashed_worker() {
+        let pool = RendererPool::new(1);
+        pool.render("@startuml\nA -> B\n@enduml").unwrap();
+
+        {
+            let mut worker = pool.checkout();
+            let process = worker.process.as_mut().unwrap();
+            process.child.kill().unwrap();
+            process.child.wait().unwrap();
+        }
+
+        assert!(pool.render("@startuml\nA -> B\n@enduml").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_renderer_times_out_on_wedged_process() {
+        let options = RenderOptions::new().timeout(Duration::from_secs(2));
+        let mut renderer = Renderer::with_options(options);
+        renderer.render("@startuml\nA -> B\n@enduml").unwrap();
+
+        let pid = renderer.process.as_ref().unwrap().child.id();
+        // SAFETY: the child is still running and owned by the renderer
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) };
+
+        // Larger than a pipe buffer, so writing it would block forever
+        let source = format!("@startuml\n{}@enduml", "A -> B\n".repeat(200_000));
+        assert!(matches!(
+            renderer.render(&source),
+            Err(PlantUmlError::Timeout(_))
+        ));
+        assert!(!renderer.is_running());
+    }
+
+    #[test]
+    fn test_diagram_count() {
+        assert_eq!(
+            diagram_count("@startuml\n@enduml\n@startwbs\n@endwbs").unwrap(),
+            2
+        );
+        assert!(matches!(
+            diagram_count("Alice -> Bob"),
+            Err(PlantUmlError::SyntaxError(_))
+        ));
+        assert!(matches!(
+            diagram_count("@startuml\nAlice -> Bob"),
+            Err(PlantUmlError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_strip_delimiter() {
+        let buf = format!("<svg/>{}\r\n", DELIMITER);
+        assert_eq!(strip_delimiter(buf.as_bytes()), Some(6));
+        assert_eq!(strip_delimiter(b"<svg/>\n"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::PlantUmlError;
+use crate::renderer::{sample_process, RendererPool};
+
+/// Results of [`load_test`].
+#[derive(Debug)]
+pub struct LoadTestReport {
+    /// Renders attempted.
+    pub renders: usize,
+    /// Renders that returned an error.
+    pub failures: usize,
+    /// The first error, to tell what went wrong.
+    pub first_error: Option<PlantUmlError>,
+    /// Wall time for all renders.
+    pub elapsed: Duration,
+    /// PlantUML processes the pool started during the test.
+    pub processes_started: usize,
+    /// Processes the pool started at any time that are neither held by an
+    /// idle worker nor stopped and reaped.
+    pub processes_leaked: usize,
+    /// Open file descriptors of this process before and after the test.
+    /// `None` outside Linux.
+    pub open_fds: Option<(usize, usize)>,
+    /// Resident memory of this process before and after the test, in
+    /// bytes. `None` outside Linux.
+    pub resident_bytes: Option<(u64, u64)>,
+}
+
+impl LoadTestReport {
+    /// Number of renders that succeeded.
+    pub fn succeeded(&self) -> usize {
+        self.renders - self.failures
+    }
+
+    /// Additional file descriptors open after the test.
+    pub fn fd_growth(&self) -> Option<usize> {
+        self.open_fds
+            .map(|(before, after)| after.saturating_sub(before))
+    }
+
+    /// Whether every render succeeded and no process or file descriptor
+    /// was left behind.
+    pub fn is_clean(&self) -> bool {
+        self.failures == 0 && self.processes_leaked == 0 && self.fd_growth().unwrap_or(0) == 0
+    }
+}
+
+/// Hammer `pool` with `renders` renders from `concurrency` threads and check
+/// it doesn't leak, e.g. to validate a server's pool configuration.
+///
+/// Sources are rendered round-robin from `sources`, with both priorities
+/// and with identical renders coalesced as usual. Process, file descriptor
+/// and memory figures are measured over this whole process, so run the
+/// test while nothing else uses the pool, and expect the first run to
+/// grow them by the workers it starts; compare a second run to find leaks.
+///
+/// # Panics
+///
+/// Panics if `sources` is empty while `renders` isn't zero.
+///
+/// # Example
+///
+/// ```no_run
+/// let pool = plantuml::RendererPool::new(4);
+/// let sources = ["@startuml\nA -> B\n@enduml", "@startuml\nB -> C\n@enduml"];
+///
+/// plantuml::load_test(&pool, &sources, 100, 8);
+/// let report = plantuml::load_test(&pool, &sources, 1000, 8);
+/// assert!(report.is_clean(), "{:?}", report);
+/// ```
+pub fn load_test(
+    pool: &RendererPool,
+    sources: &[&str],
+    renders: usize,
+    concurrency: usize,
+) -> LoadTestReport {
+    assert!(
+        renders == 0 || !sources.is_empty(),
+        "load_test needs at least one source"
+    );
+
+    let started_before = pool.stats().processes_started;
+    let fds_before = open_fds();
+    let resident_before = resident_bytes();
+    let started = Instant::now();
+
+    let next = AtomicUsize::new(0);
+    let failures = AtomicUsize::new(0);
+    let first_error = Mutex::new(None);
+    thread::scope(|s| {
+        for _ in 0..concurrency.max(1) {
+            s.spawn(|| loop {
+                let n = next.fetch_add(1, Ordering::SeqCst);
+                if n >= renders {
+                    return;
+                }
+                let priority = match n % 2 {
+                    0 => crate::renderer::Priority::Interactive,
+                    _ => crate::renderer::Priority::Batch,
+                };
+                if let Err(e) = pool.render_with_priority(sources[n % sources.len()], priority) {
+                    failures.fetch_add(1, Ordering::SeqCst);
+                    first_error
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .get_or_insert(e);
+                }
+            });
+        }
+    });
+
+    let elapsed = started.elapsed();
+    let stats = pool.stats();
+    let processes = stats.processes_started - stats.processes_stopped;
+
+    LoadTestReport {
+        renders,
+        failures: failures.into_inner(),
+        first_error: first_error.into_inner().unwrap_or_else(|e| e.into_inner()),
+        elapsed,
+        processes_started: stats.processes_started - started_before,
+        processes_leaked: processes.saturating_sub(pool.idle_processes()),
+        open_fds: fds_before.zip(open_fds()),
+        resident_bytes: resident_before.zip(resident_bytes()),
+    }
+}
+
+/// Number of file descriptors this process has open.
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<usize> {
+    Some(std::fs::read_dir("/proc/self/fd").ok()?.count())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<usize> {
+    None
+}
+
+fn resident_bytes() -> Option<u64> {
+    sample_process(std::process::id()).map(|sample| sample.resident_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::PoolOptions;
+    use crate::RenderOptions;
+
+    #[test]
+    fn test_load_test() {
+        let pool_options = PoolOptions {
+            min_workers: 1,
+            max_workers: 3,
+            idle_timeout: None,
+        };
+        let pool = RendererPool::with_pool_options(pool_options, RenderOptions::new());
+        let sources = [
+            "@startuml\nAlice -> Bob\n@enduml",
+            "@startuml\nBob -> Carol\n@enduml",
+            "@startuml\nCarol -> Dave\n@enduml",
+        ];
+
+        let report = load_test(&pool, &sources, 30, 6);
+        assert_eq!(report.renders, 30);
+        assert_eq!(report.failures, 0, "{:?}", report.first_error);
+        assert_eq!(report.succeeded(), 30);
+        assert!((1..=3).contains(&report.processes_started));
+        assert_eq!(report.processes_leaked, 0);
+        if cfg!(target_os = "linux") {
+            assert!(report.open_fds.is_some());
+            assert!(report.resident_bytes.is_some());
+        }
+
+        // Workers killed mid-test are counted as stopped, not leaked
+        let mut workers: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+        workers.iter_mut().for_each(|worker| worker.kill_process());
+        drop(workers);
+        let report = load_test(&pool, &sources, 6, 3);
+        assert_eq!(report.failures, 0, "{:?}", report.first_error);
+        assert_eq!(report.processes_leaked, 0);
+
+        drop(pool);
+    }
+
+    #[test]
+    fn test_load_test_reports_failures() {
+        let pool = RendererPool::new(1);
+
+        let report = load_test(&pool, &["@startuml\nA -> B\n"], 2, 2);
+        assert_eq!(report.failures, 2);
+        assert!(matches!(
+            report.first_error,
+            Some(PlantUmlError::SyntaxError(_))
+        ));
+        assert!(!report.is_clean());
+    }
+}
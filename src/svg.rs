@@ -0,0 +1,250 @@
+/// Accessibility metadata to inject into a rendered SVG.
+#[derive(Debug, Clone, Default)]
+pub struct Accessibility {
+    /// Short accessible name, emitted as `<title>` and `aria-label`.
+    pub title: Option<String>,
+    /// Longer description (alt text), emitted as `<desc>`.
+    pub description: Option<String>,
+}
+
+impl Accessibility {
+    /// Build accessibility metadata from the diagram's `title` line and optional alt text.
+    pub fn from_source(plantuml: &str, alt_text: Option<&str>) -> Self {
+        Self {
+            title: diagram_title(plantuml),
+            description: alt_text.map(str::to_string),
+        }
+    }
+}
+
+/// Inject `<title>`/`<desc>` elements and ARIA attributes into the SVG root.
+///
+/// The input is returned unchanged if it has no `<svg>` root element.
+pub fn add_accessibility(svg: &str, a11y: &Accessibility) -> String {
+    let Some((tag_start, tag_end)) = find_root_tag(svg) else {
+        return svg.to_string();
+    };
+    let root_tag = &svg[tag_start..tag_end];
+    let self_closing = root_tag.ends_with("/>");
+    let attrs_end = if self_closing {
+        tag_end - 2
+    } else {
+        tag_end - 1
+    };
+
+    let mut attrs = String::new();
+    if !root_tag.contains(" role=") {
+        attrs.push_str(" role=\"img\"");
+    }
+    if let Some(title) = a11y.title.as_deref().or(a11y.description.as_deref()) {
+        if !root_tag.contains(" aria-label=") {
+            attrs.push_str(&format!(" aria-label=\"{}\"", escape(title)));
+        }
+    }
+
+    let mut children = String::new();
+    if let Some(title) = &a11y.title {
+        children.push_str(&format!("<title>{}</title>", escape(title)));
+    }
+    if let Some(description) = &a11y.description {
+        children.push_str(&format!("<desc>{}</desc>", escape(description)));
+    }
+
+    let mut out = String::with_capacity(svg.len() + attrs.len() + children.len() + 8);
+    out.push_str(&svg[..attrs_end]);
+    out.push_str(&attrs);
+    if self_closing {
+        out.push('>');
+        out.push_str(&children);
+        out.push_str("</svg>");
+    } else {
+        out.push('>');
+        out.push_str(&children);
+    }
+    out.push_str(&svg[tag_end..]);
+    out
+}
+
+/// Resize an SVG to exactly `width` x `height` pixels, preserving its aspect ratio.
+///
+/// The root element gets the new `width`/`height`, a `viewBox` covering the
+/// original drawing (derived from the original size if missing) and
+/// `preserveAspectRatio="xMidYMid meet"`, so the drawing is scaled to fit
+/// and centered. Inline `width`/`height` styles are updated to match.
+pub fn fit(svg: &str, width: u32, height: u32) -> String {
+    let Some((tag_start, tag_end)) = find_root_tag(svg) else {
+        return svg.to_string();
+    };
+    let root_tag = &svg[tag_start..tag_end];
+    let self_closing = root_tag.ends_with("/>");
+    let body = root_tag["<svg".len()..root_tag.len() - if self_closing { 2 } else { 1 }].trim();
+    let mut attrs = parse_attributes(body);
+
+    let has_view_box = attrs.iter().any(|(name, _)| name == "viewBox");
+    if !has_view_box {
+        let size = |name: &str| {
+            attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .and_then(|(_, v)| v.trim_end_matches("px").parse::<f64>().ok())
+        };
+        if let (Some(w), Some(h)) = (size("width"), size("height")) {
+            attrs.push(("viewBox".to_string(), format!("0 0 {} {}", w, h)));
+        }
+    }
+
+    set_attribute(&mut attrs, "width", format!("{}px", width));
+    set_attribute(&mut attrs, "height", format!("{}px", height));
+    set_attribute(
+        &mut attrs,
+        "preserveAspectRatio",
+        "xMidYMid meet".to_string(),
+    );
+    if let Some((_, style)) = attrs.iter_mut().find(|(name, _)| name == "style") {
+        *style = style
+            .split(';')
+            .filter(|decl| !decl.trim().is_empty())
+            .map(|decl| match decl.split_once(':').map(|(k, _)| k.trim()) {
+                Some("width") => format!("width:{}px", width),
+                Some("height") => format!("height:{}px", height),
+                _ => decl.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(";")
+            + ";";
+    }
+
+    let mut out = String::with_capacity(svg.len() + 64);
+    out.push_str(&svg[..tag_start]);
+    out.push_str("<svg");
+    for (name, value) in &attrs {
+        out.push_str(&format!(" {}=\"{}\"", name, value));
+    }
+    out.push_str(if self_closing { "/>" } else { ">" });
+    out.push_str(&svg[tag_end..]);
+    out
+}
+
+/// Parse `name="value"` pairs of a start tag, keeping values escaped as-is.
+fn parse_attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().to_string();
+        let value_part = rest[eq + 1..].trim_start();
+        let Some(quote) = value_part
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+        else {
+            break;
+        };
+        let Some(len) = value_part[1..].find(quote) else {
+            break;
+        };
+
+        attrs.push((name, value_part[1..1 + len].replace('"', "&quot;")));
+        rest = &value_part[len + 2..];
+    }
+
+    attrs
+}
+
+fn set_attribute(attrs: &mut Vec<(String, String)>, name: &str, value: String) {
+    match attrs.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => *v = value,
+        None => attrs.push((name.to_string(), value)),
+    }
+}
+
+/// Return the text of a single-line `title` directive in the diagram source.
+pub(crate) fn diagram_title(plantuml: &str) -> Option<String> {
+    plantuml.lines().find_map(|line| {
+        let line = line.trim();
+        let keyword = line.get(..6)?;
+        if !keyword.eq_ignore_ascii_case("title ") {
+            return None;
+        }
+        let title = line[6..].trim();
+        (!title.is_empty()).then(|| title.to_string())
+    })
+}
+
+/// Locate the byte range of the root `<svg ...>` start tag, honoring quoted attributes.
+pub(crate) fn find_root_tag(svg: &str) -> Option<(usize, usize)> {
+    let start = svg.find("<svg")?;
+    let mut quote = None;
+
+    for (i, c) in svg[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some((start, start + i + 1)),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagram_title() {
+        let source = "@startuml\nTitle  Login <Flow>\nAlice -> Bob\n@enduml";
+        assert_eq!(diagram_title(source).as_deref(), Some("Login <Flow>"));
+        assert_eq!(diagram_title("@startuml\nA -> B\n@enduml"), None);
+    }
+
+    #[test]
+    fn test_add_accessibility() {
+        let svg = r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" data-x="a>b"><g/></svg>"#;
+        let a11y = Accessibility {
+            title: Some("Login <Flow>".to_string()),
+            description: Some("Alice greets Bob".to_string()),
+        };
+
+        let out = add_accessibility(svg, &a11y);
+        assert_eq!(
+            out,
+            r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" data-x="a>b" role="img" aria-label="Login &lt;Flow&gt;"><title>Login &lt;Flow&gt;</title><desc>Alice greets Bob</desc><g/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_fit() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" height="120px" preserveAspectRatio="none" style="width:113px;height:120px;background:#FFFFFF;" viewBox="0 0 113 120" width="113px"><g/></svg>"#;
+
+        assert_eq!(
+            fit(svg, 800, 600),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" height="600px" preserveAspectRatio="xMidYMid meet" style="width:800px;height:600px;background:#FFFFFF;" viewBox="0 0 113 120" width="800px"><g/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_fit_adds_view_box() {
+        let svg = r#"<svg width="100px" height="50px"/>"#;
+
+        assert_eq!(
+            fit(svg, 200, 200),
+            r#"<svg width="200px" height="200px" viewBox="0 0 100 50" preserveAspectRatio="xMidYMid meet"/>"#
+        );
+    }
+
+    #[test]
+    fn test_add_accessibility_without_svg_root() {
+        assert_eq!(
+            add_accessibility("not svg", &Accessibility::default()),
+            "not svg"
+        );
+    }
+}
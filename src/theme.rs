@@ -0,0 +1,52 @@
+use std::fs;
+use std::io;
+
+use crate::binary::get_bundle_paths;
+use crate::error::{PlantUmlError, Result};
+
+/// Directory and file name pattern of the theme files inside the PlantUML JAR
+const THEME_PREFIX: &str = "themes/puml-theme-";
+const THEME_SUFFIX: &str = ".puml";
+
+/// List the themes shipped inside the bundled PlantUML JAR, sorted by name.
+///
+/// Any of these can be applied offline with
+/// [`RenderOptions::theme`](crate::RenderOptions::theme) or a `!theme`
+/// directive.
+///
+/// # Example
+///
+/// ```no_run
+/// let themes = plantuml::list_themes().unwrap();
+/// assert!(themes.iter().any(|t| t == "cerulean"));
+/// ```
+pub fn list_themes() -> Result<Vec<String>> {
+    let paths = get_bundle_paths()?;
+    let jar = fs::File::open(&paths.plantuml_jar).map_err(PlantUmlError::BinaryExtraction)?;
+    let archive = zip::ZipArchive::new(jar)
+        .map_err(|e| PlantUmlError::BinaryExtraction(io::Error::other(e)))?;
+
+    let mut themes: Vec<String> = archive
+        .file_names()
+        .filter_map(|name| name.strip_prefix(THEME_PREFIX)?.strip_suffix(THEME_SUFFIX))
+        .map(str::to_string)
+        .collect();
+    themes.sort();
+
+    Ok(themes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_themes() {
+        let themes = list_themes();
+        assert!(themes.is_ok(), "Failed to list themes: {:?}", themes);
+
+        let themes = themes.unwrap();
+        assert!(!themes.is_empty(), "Bundled JAR should ship themes");
+        assert!(themes.windows(2).all(|w| w[0] <= w[1]));
+    }
+}